@@ -2,15 +2,86 @@ use darling::{ast, FromDeriveInput, FromVariant};
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
 
-use crate::types::Paths;
+use crate::types::{rename_ident, Paths, RenameRule};
 
 #[derive(Debug, FromDeriveInput)]
-#[darling(attributes(protobuf_mapper), supports(enum_unit))]
+#[darling(attributes(protobuf_mapper), supports(enum_unit, enum_newtype))]
 pub struct InputReceiver {
   ident: syn::Ident,
   generics: syn::Generics,
   data: ast::Data<VariantReceiver, ()>,
   proto_enum_type: Paths,
+  /// Proto variant with no Rust counterpart, usually the zero `UNSPECIFIED` value.
+  #[darling(default)]
+  unspecified: Option<syn::Ident>,
+  /// Implement `ProtoEnumName` with prost's `as_str_name` and `from_str_name`.
+  #[darling(default)]
+  proto_names: bool,
+  /// Implement `FromStr` with `ProtoEnumName::from_proto_name`. Implies `proto_names`.
+  #[darling(default)]
+  from_str: bool,
+  /// Implement `Display` with `ProtoEnumName::proto_name`. Implies `proto_names`.
+  #[darling(default)]
+  display: bool,
+  /// Case convention of the proto variants, applied to variants without `rename`.
+  #[darling(default)]
+  rename_all: Option<RenameRule>,
+  /// Prepended to the proto variants after `rename_all`, e.g. `ORDER_STATUS_`.
+  #[darling(default)]
+  prefix: Option<String>,
+}
+
+impl InputReceiver {
+  /// Only the `other` variant may carry a field, the raw discriminant. `Display` and `FromStr`
+  /// can only use the names of one proto enum type.
+  pub fn validate(self) -> darling::Result<Self> {
+    let mut errors = vec![];
+    if (self.display || self.from_str) && self.proto_enum_type.paths.len() > 1 {
+      errors.push(
+        darling::Error::custom("`display` and `from_str` need a single `proto_enum_type`")
+          .with_span(&self.ident),
+      );
+    }
+    let mut others = 0;
+    for v in self
+      .data
+      .as_ref()
+      .take_enum()
+      .expect("Should never be struct")
+    {
+      if let Err(err) = self.proto_ident(v) {
+        errors.push(err);
+      }
+      if v.other {
+        others += 1;
+        if v.fields.style != ast::Style::Tuple {
+          errors.push(
+            darling::Error::custom("`other` variant must be like `Unknown(i32)`")
+              .with_span(&v.ident),
+          );
+        } else if others > 1 {
+          errors
+            .push(darling::Error::custom("only one variant can be `other`").with_span(&v.ident));
+        }
+      } else if !v.fields.is_unit() {
+        errors.push(darling::Error::unsupported_shape("newtype").with_span(&v.ident));
+      }
+    }
+    if errors.is_empty() {
+      Ok(self)
+    } else {
+      Err(darling::Error::multiple(errors))
+    }
+  }
+
+  /// Variant of the proto enum that `v` maps to.
+  fn proto_ident(&self, v: &VariantReceiver) -> darling::Result<syn::Ident> {
+    if let Some(ref ident) = v.rename {
+      Ok(ident.clone())
+    } else {
+      rename_ident(&v.ident, self.rename_all, self.prefix.as_deref(), None)
+    }
+  }
 }
 
 impl ToTokens for InputReceiver {
@@ -20,10 +91,16 @@ impl ToTokens for InputReceiver {
       ref generics,
       ref data,
       ref proto_enum_type,
+      ref unspecified,
+      proto_names,
+      from_str,
+      display,
+      ..
     } = *self;
 
     let (imp, ty, wher) = generics.split_for_impl();
     let variants = data.as_ref().take_enum().expect("Should never be struct");
+    let other = variants.iter().find(|v| v.other);
 
     let names: Vec<_> = variants
       .iter()
@@ -31,11 +108,19 @@ impl ToTokens for InputReceiver {
         let v_ident = &v.ident;
         let v_name = v.ident.to_string();
         quote! {
-          Self::#v_ident => #v_name,
+          Self::#v_ident { .. } => #v_name,
         }
       })
       .collect();
 
+    let known: Vec<_> = variants.iter().filter(|v| !v.other).collect();
+    let known_idents: Vec<_> = known.iter().map(|v| &v.ident).collect();
+    let known_names: Vec<_> = known.iter().map(|v| v.ident.to_string()).collect();
+    let proto_idents: Vec<_> = known
+      .iter()
+      .map(|v| self.proto_ident(v).expect("Checked by validate"))
+      .collect();
+
     let name = ident.to_string();
 
     tokens.extend(quote! {
@@ -48,16 +133,143 @@ impl ToTokens for InputReceiver {
           }
         }
       }
+
+      impl #imp protobuf_mapper::ProtoEnumVariants for #ident #ty #wher {
+        const VARIANTS: &'static [Self] = &[#(Self::#known_idents),*];
+
+        fn from_variant_name(name: &str) -> Option<Self> {
+          match name {
+            #(#known_names => Some(Self::#known_idents),)*
+            _ => None,
+          }
+        }
+      }
     });
 
+    if proto_names || from_str || display {
+      let other_arm = other.map(|v| {
+        let v_ident = &v.ident;
+        quote! {
+          Self::#v_ident(_) => None,
+        }
+      });
+      for proto_enum_type in &proto_enum_type.paths {
+        tokens.extend(quote! {
+          impl #imp protobuf_mapper::ProtoEnumName<#proto_enum_type> for #ident #ty #wher {
+            fn proto_name(&self) -> Option<&'static str> {
+              match *self {
+                #(Self::#known_idents => Some(#proto_enum_type::#proto_idents.as_str_name()),)*
+                #other_arm
+              }
+            }
+
+            fn from_proto_name(name: &str) -> Option<Self> {
+              #proto_enum_type::from_str_name(name)
+                .and_then(|p| <Self as protobuf_mapper::ProtoEnum<#proto_enum_type>>::unpack_enum(p).ok())
+            }
+          }
+        });
+      }
+    }
+
+    if from_str {
+      let proto_enum_type = &proto_enum_type.paths[0];
+      // `Display` writes the discriminant of an `other` variant, so read it back.
+      let other_parse = other.map(|v| {
+        let v_ident = &v.ident;
+        quote! {
+          if let Ok(v) = s.parse::<i32>() {
+            if #proto_enum_type::from_i32(v).is_none() {
+              return Ok(Self::#v_ident(v));
+            }
+          }
+        }
+      });
+      tokens.extend(quote! {
+        impl #imp std::str::FromStr for #ident #ty #wher {
+          type Err = protobuf_mapper::result::Error;
+
+          fn from_str(s: &str) -> protobuf_mapper::result::Result<Self> {
+            if let Some(v) = <Self as protobuf_mapper::ProtoEnumName<#proto_enum_type>>::from_proto_name(s) {
+              return Ok(v);
+            }
+            #other_parse
+            Err(protobuf_mapper::result::Error::EnumNameNotFound {
+              enum_name: <Self as protobuf_mapper::ProtoEnumMeta>::NAME,
+              name: s.to_string(),
+            })
+          }
+        }
+      });
+    }
+
+    if display {
+      let proto_enum_type = &proto_enum_type.paths[0];
+      let other_arm = other.map(|v| {
+        let v_ident = &v.ident;
+        quote! {
+          Self::#v_ident(v) => write!(f, "{}", v),
+        }
+      });
+      tokens.extend(quote! {
+        impl #imp std::fmt::Display for #ident #ty #wher {
+          fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match *self {
+              #other_arm
+              ref value => f.write_str(
+                <Self as protobuf_mapper::ProtoEnumName<#proto_enum_type>>::proto_name(value)
+                  .expect("Only `other` has no proto name"),
+              ),
+            }
+          }
+        }
+      });
+    }
+
+    let s2p = |proto_enum_type: &syn::Path| -> Vec<TokenStream> {
+      known_idents
+        .iter()
+        .zip(&proto_idents)
+        .map(|(v_ident, proto_ident)| {
+          quote! {
+            Self::#v_ident => #proto_enum_type::#proto_ident,
+          }
+        })
+        .collect()
+    };
+
+    if unspecified.is_some() {
+      tokens.extend(quote! {
+        impl #imp protobuf_mapper::ProtoEnumUnspecified for #ident #ty #wher {}
+      });
+    }
+
     if proto_enum_type.paths.len() == 1 {
       let proto_enum_type = &proto_enum_type.paths[0];
+      let s2p = s2p(proto_enum_type);
+
+      let other_arm = other.map(|other| {
+        let other_ident = &other.ident;
+        quote! {
+          Self::#other_ident(v) => return Ok(v),
+        }
+      });
 
       tokens.extend(quote! {
         impl #imp protobuf_mapper::ProtoPack<i32> for #ident #ty #wher
         {
           fn pack(self) -> protobuf_mapper::result::Result<i32> {
-            let v = <Self as protobuf_mapper::ProtoEnum<#proto_enum_type>>::into_proto_enum(self);
+            <Self as protobuf_mapper::ProtoEnum<#proto_enum_type>>::pack_i32(self)
+          }
+        }
+
+        impl #imp protobuf_mapper::ProtoPackRef<i32> for #ident #ty #wher
+        {
+          fn pack_ref(&self) -> protobuf_mapper::result::Result<i32> {
+            let v: #proto_enum_type = match *self {
+              #other_arm
+              #(#s2p)*
+            };
             Ok(v.into())
           }
         }
@@ -72,28 +284,19 @@ impl ToTokens for InputReceiver {
     }
 
     for proto_enum_type in &proto_enum_type.paths {
-      let (s2p, p2s): (Vec<_>, Vec<_>) = variants
+      let s2p = s2p(proto_enum_type);
+      let p2s: Vec<_> = known_idents
         .iter()
-        .map(|v| {
-          let v_ident = &v.ident;
-          let proto_ident = if let Some(ref ident) = v.rename {
-            ident
-          } else {
-            v_ident
-          };
-          (
-            quote! {
-              Self::#v_ident => #proto_enum_type::#proto_ident,
-            },
-            quote! {
-              #proto_enum_type::#proto_ident => Self::#v_ident,
-            },
-          )
+        .zip(&proto_idents)
+        .map(|(v_ident, proto_ident)| {
+          quote! {
+            #proto_enum_type::#proto_ident => Self::#v_ident,
+          }
         })
-        .unzip();
+        .collect();
 
-      tokens.extend(quote! {
-        impl #imp protobuf_mapper::ProtoEnum<#proto_enum_type> for #ident #ty  #wher {
+      let unpack_fns = if unspecified.is_none() && other.is_none() {
+        quote! {
           fn from_i32(v: i32) -> Option<Self> {
             #proto_enum_type::from_i32(v)
               .map(|p| {
@@ -103,18 +306,126 @@ impl ToTokens for InputReceiver {
               })
           }
 
+          fn unpack_enum(v: #proto_enum_type) -> protobuf_mapper::result::Result<Self> {
+            Ok(match v {
+              #(#p2s)*
+            })
+          }
+        }
+      } else {
+        let (unknown, unknown_result) = if let Some(other) = other {
+          let other_ident = &other.ident;
+          (
+            quote! { Some(Self::#other_ident(v)) },
+            quote! { Ok(Self::#other_ident(v)) },
+          )
+        } else {
+          (
+            quote! { None },
+            quote! {
+              Err(protobuf_mapper::result::Error::EnumDiscriminantNotFound {
+                enum_name: <Self as protobuf_mapper::ProtoEnumMeta>::NAME,
+                discriminant: v,
+              })
+            },
+          )
+        };
+        let unspecified_arm = unspecified.as_ref().map(|unspecified| {
+          quote! {
+            #proto_enum_type::#unspecified => Err(protobuf_mapper::result::Error::EnumUnspecified {
+              enum_name: <Self as protobuf_mapper::ProtoEnumMeta>::NAME,
+            }),
+          }
+        });
+        let p2s_ok = known_idents
+          .iter()
+          .zip(&proto_idents)
+          .map(|(v_ident, proto_ident)| {
+            quote! {
+              #proto_enum_type::#proto_ident => Ok(Self::#v_ident),
+            }
+          });
+        quote! {
+          fn from_i32(v: i32) -> Option<Self> {
+            match #proto_enum_type::from_i32(v) {
+              Some(p) => <Self as protobuf_mapper::ProtoEnum<#proto_enum_type>>::unpack_enum(p).ok(),
+              None => #unknown,
+            }
+          }
+
+          fn unpack_i32(v: i32) -> protobuf_mapper::result::Result<Self>
+          where
+            Self: protobuf_mapper::ProtoEnumMeta,
+          {
+            match #proto_enum_type::from_i32(v) {
+              Some(p) => <Self as protobuf_mapper::ProtoEnum<#proto_enum_type>>::unpack_enum(p),
+              None => #unknown_result,
+            }
+          }
+
+          fn unpack_enum(v: #proto_enum_type) -> protobuf_mapper::result::Result<Self> {
+            match v {
+              #(#p2s_ok)*
+              #unspecified_arm
+            }
+          }
+        }
+      };
+
+      let other_fns = other.map(|other| {
+        // The proto enum can't hold an unknown value; `pack_i32` keeps it.
+        let other_ident = &other.ident;
+        let proto_enum_name = proto_enum_type
+          .segments
+          .last()
+          .expect("Should never be empty")
+          .ident
+          .to_string();
+        quote! {
           fn into_proto_enum(self) -> #proto_enum_type {
             match self {
+              Self::#other_ident(_) => Default::default(),
               #(#s2p)*
             }
           }
 
-          fn unpack_enum(v: #proto_enum_type) -> Self {
-            match v {
-              #(#p2s)*
+          fn try_into_proto_enum(self) -> protobuf_mapper::result::Result<#proto_enum_type> {
+            match self {
+              Self::#other_ident(v) => Err(protobuf_mapper::result::Error::EnumDiscriminantNotFound {
+                enum_name: #proto_enum_name,
+                discriminant: v,
+              }),
+              value => Ok(<Self as protobuf_mapper::ProtoEnum<#proto_enum_type>>::into_proto_enum(value)),
+            }
+          }
+
+          fn pack_i32(self) -> protobuf_mapper::result::Result<i32>
+          where
+            #proto_enum_type: Into<i32>,
+          {
+            match self {
+              Self::#other_ident(v) => Ok(v),
+              value => Ok(<Self as protobuf_mapper::ProtoEnum<#proto_enum_type>>::into_proto_enum(value).into()),
             }
           }
         }
+      });
+      let proto_fns = other_fns.unwrap_or_else(|| {
+        quote! {
+          fn into_proto_enum(self) -> #proto_enum_type {
+            match self {
+              #(#s2p)*
+            }
+          }
+        }
+      });
+
+      tokens.extend(quote! {
+        impl #imp protobuf_mapper::ProtoEnum<#proto_enum_type> for #ident #ty  #wher {
+          #proto_fns
+
+          #unpack_fns
+        }
       })
     }
   }
@@ -124,6 +435,10 @@ impl ToTokens for InputReceiver {
 #[darling(attributes(protobuf_mapper))]
 struct VariantReceiver {
   ident: syn::Ident,
+  fields: ast::Fields<()>,
   #[darling(default)]
   rename: Option<syn::Ident>,
+  /// Catch-all `Unknown(i32)` variant for discriminants the proto enum doesn't know.
+  #[darling(default)]
+  other: bool,
 }
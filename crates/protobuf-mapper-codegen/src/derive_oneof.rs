@@ -0,0 +1,242 @@
+use darling::{ast, FromDeriveInput, FromField, FromVariant};
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+
+use crate::types::{InputType, Paths};
+
+#[derive(Debug, FromDeriveInput)]
+#[darling(attributes(protobuf_mapper), supports(enum_newtype, enum_named))]
+pub struct InputReceiver {
+  #[darling(skip)]
+  input_type: InputType,
+  ident: syn::Ident,
+  generics: syn::Generics,
+  data: ast::Data<VariantReceiver, ()>,
+  oneof_type: Paths,
+  /// Also implement `ProtoPackRef`, which needs `ProtoPackRef` on every payload.
+  #[darling(default)]
+  pack_ref: bool,
+}
+
+impl InputReceiver {
+  pub fn into_unpack(self) -> Self {
+    Self {
+      input_type: InputType::Unpack,
+      ..self
+    }
+  }
+
+  /// Each variant maps to exactly one oneof case, so it must carry exactly one payload field.
+  pub fn validate(self) -> darling::Result<Self> {
+    let mut errors = vec![];
+    for v in self
+      .data
+      .as_ref()
+      .take_enum()
+      .expect("Should never be struct")
+    {
+      if v.fields.len() != 1 {
+        errors.push(
+          darling::Error::custom("oneof variant must have exactly one field").with_span(&v.ident),
+        );
+      }
+    }
+    if errors.is_empty() {
+      Ok(self)
+    } else {
+      Err(darling::Error::multiple(errors))
+    }
+  }
+}
+
+impl ToTokens for InputReceiver {
+  fn to_tokens(&self, tokens: &mut TokenStream) {
+    let InputReceiver {
+      input_type,
+      ref ident,
+      ref generics,
+      ref data,
+      ref oneof_type,
+      pack_ref,
+    } = *self;
+
+    let (imp, ty, wher) = generics.split_for_impl();
+    let variants = data.as_ref().take_enum().expect("Should never be struct");
+
+    for oneof_type in &oneof_type.paths {
+      match input_type {
+        InputType::Pack => {
+          let (arms, ref_arms): (Vec<_>, Vec<_>) = variants
+            .iter()
+            .map(|v| {
+              let v_ident = &v.ident;
+              let case_ident = v.rename.as_ref().unwrap_or(v_ident);
+              let pattern = v.pattern();
+              (
+                quote! {
+                  Self::#v_ident #pattern => #oneof_type::#case_ident(payload.pack()?),
+                },
+                quote! {
+                  Self::#v_ident #pattern => #oneof_type::#case_ident(
+                    protobuf_mapper::ProtoPackRef::pack_ref(payload)?
+                  ),
+                },
+              )
+            })
+            .unzip();
+
+          let pack_block = quote! {
+            match value {
+              #(#arms)*
+            }
+          };
+
+          let ref_pack_block = quote! {
+            match value {
+              #(#ref_arms)*
+            }
+          };
+
+          tokens.extend(quote! {
+            impl #imp protobuf_mapper::ProtoPack<#oneof_type> for #ident #ty #wher {
+              fn pack(self) -> protobuf_mapper::result::Result<#oneof_type> {
+                let value = self;
+                Ok(#pack_block)
+              }
+            }
+
+            impl #imp protobuf_mapper::ProtoPack<Option<#oneof_type>> for #ident #ty #wher {
+              fn pack(self) -> protobuf_mapper::result::Result<Option<#oneof_type>> {
+                let value = self;
+                Ok(Some(#pack_block))
+              }
+            }
+          });
+
+          if !pack_ref {
+            continue;
+          }
+
+          tokens.extend(quote! {
+            impl #imp protobuf_mapper::ProtoPackRef<#oneof_type> for #ident #ty #wher {
+              fn pack_ref(&self) -> protobuf_mapper::result::Result<#oneof_type> {
+                let value = self;
+                Ok(#ref_pack_block)
+              }
+            }
+
+            impl #imp protobuf_mapper::ProtoPackRef<Option<#oneof_type>> for #ident #ty #wher {
+              fn pack_ref(&self) -> protobuf_mapper::result::Result<Option<#oneof_type>> {
+                let value = self;
+                Ok(Some(#ref_pack_block))
+              }
+            }
+          });
+        }
+        InputType::Unpack => {
+          let (arms, collect_arms): (Vec<_>, Vec<_>) = variants
+            .iter()
+            .map(|v| {
+              let v_ident = &v.ident;
+              let case_ident = v.rename.as_ref().unwrap_or(v_ident);
+              let pattern = v.pattern();
+              let case_name = case_ident.to_string().to_snake_case();
+              (
+                quote! {
+                  #oneof_type::#case_ident(payload) => {
+                    let payload = ProtoUnpack::unpack(payload).map_err(|err| {
+                      protobuf_mapper::result::Error::field(#case_name, err)
+                    })?;
+                    Self::#v_ident #pattern
+                  }
+                },
+                quote! {
+                  #oneof_type::#case_ident(payload) => {
+                    let payload = ProtoUnpack::unpack_all_errors(payload).map_err(|errs| {
+                      errs
+                        .into_iter()
+                        .map(|err| protobuf_mapper::result::Error::field(#case_name, err))
+                        .collect::<Vec<_>>()
+                    })?;
+                    Self::#v_ident #pattern
+                  }
+                },
+              )
+            })
+            .unzip();
+
+          let unpack_block = quote! {
+            Ok(match value {
+              #(#arms)*
+            })
+          };
+
+          let collect_block = quote! {
+            Ok(match value {
+              #(#collect_arms)*
+            })
+          };
+
+          tokens.extend(quote! {
+            impl #imp protobuf_mapper::ProtoUnpack<#oneof_type> for #ident #ty #wher {
+              fn unpack(value: #oneof_type) -> protobuf_mapper::result::Result<Self> {
+                #unpack_block
+              }
+
+              fn unpack_all_errors(value: #oneof_type) -> protobuf_mapper::result::Result<Self, protobuf_mapper::result::Errors> {
+                #collect_block
+              }
+            }
+
+            impl #imp protobuf_mapper::ProtoUnpack<Option<#oneof_type>> for #ident #ty #wher {
+              fn unpack(value: Option<#oneof_type>) -> protobuf_mapper::result::Result<Self> {
+                if let Some(value) = value {
+                  #unpack_block
+                } else {
+                  Err(protobuf_mapper::result::Error::OneofNotPresent)
+                }
+              }
+
+              fn unpack_all_errors(value: Option<#oneof_type>) -> protobuf_mapper::result::Result<Self, protobuf_mapper::result::Errors> {
+                if let Some(value) = value {
+                  #collect_block
+                } else {
+                  Err(protobuf_mapper::result::Error::OneofNotPresent.into())
+                }
+              }
+            }
+          });
+        }
+      }
+    }
+  }
+}
+
+#[derive(Debug, FromVariant)]
+#[darling(attributes(protobuf_mapper))]
+struct VariantReceiver {
+  ident: syn::Ident,
+  fields: ast::Fields<VariantFieldReceiver>,
+  #[darling(default)]
+  rename: Option<syn::Ident>,
+}
+
+impl VariantReceiver {
+  /// Binds the payload of a newtype or single named field variant to `payload`.
+  fn pattern(&self) -> TokenStream {
+    match self.fields.style {
+      ast::Style::Struct => {
+        let field_ident = &self.fields.fields[0].ident;
+        quote! { { #field_ident: payload } }
+      }
+      _ => quote! { (payload) },
+    }
+  }
+}
+
+#[derive(Debug, FromField)]
+#[darling(attributes(protobuf_mapper))]
+struct VariantFieldReceiver {
+  ident: Option<syn::Ident>,
+}
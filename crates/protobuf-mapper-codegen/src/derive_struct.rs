@@ -1,39 +1,318 @@
-use darling::{ast, FromDeriveInput, FromField};
+use darling::{ast, util::Override, FromDeriveInput, FromField, FromMeta};
 use proc_macro2::{Span, TokenStream};
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::ext::IdentExt;
 
-use crate::types::Paths;
-
-#[derive(Debug, Copy, Clone)]
-enum InputType {
-  Pack,
-  Unpack,
-}
-
-impl Default for InputType {
-  fn default() -> InputType {
-    InputType::Pack
-  }
-}
+use crate::types::{rename_ident, InputType, Paths, RenameRule};
 
 #[derive(Debug, FromDeriveInput)]
-#[darling(attributes(protobuf_mapper), supports(struct_named))]
+#[darling(
+  attributes(protobuf_mapper),
+  supports(struct_named, struct_newtype, struct_tuple)
+)]
 pub struct InputReceiver {
   #[darling(skip)]
   input_type: InputType,
   ident: syn::Ident,
   generics: syn::Generics,
   data: ast::Data<(), FieldReceiver>,
-  message_type: Paths,
+  /// Omitted on a newtype struct to convert transparently through its field.
+  #[darling(default)]
+  message_type: Option<Paths>,
+  /// Fill protobuf fields without a Rust counterpart with `Default::default()` on pack.
+  #[darling(default)]
+  pack_defaults: bool,
+  /// Checks the unpacked value, see `validate_expr`.
+  #[darling(default, rename = "validate")]
+  validate_fn: Option<syn::Path>,
+  /// Case convention of the protobuf fields, applied to fields without `rename`.
+  #[darling(default)]
+  rename_all: Option<RenameRule>,
+  /// Prepended to the protobuf fields after `rename_all`.
+  #[darling(default)]
+  field_prefix: Option<String>,
+  /// Appended to the protobuf fields after `rename_all`.
+  #[darling(default)]
+  field_suffix: Option<String>,
+  /// Fail to compile if the message has fields the struct doesn't map, see `exhaustive_check`.
+  #[darling(default)]
+  exhaustive: bool,
+  /// Message fields an `exhaustive` struct leaves out: unread on unpack, defaulted on pack.
+  #[darling(default)]
+  ignore_proto_fields: Option<Paths>,
+  /// Also implement `ProtoPackRef`, which needs `ProtoPackRef` on every packed field.
+  #[darling(default)]
+  pack_ref: bool,
+  /// Makes `proto_enum` fields `strict` unless they opt out with `strict = false`.
+  #[darling(default)]
+  strict_enums: bool,
 }
 
 impl InputReceiver {
-  pub fn to_unpack(self) -> Self {
+  pub fn into_unpack(self) -> Self {
     Self {
       input_type: InputType::Unpack,
       ..self
     }
   }
+
+  /// Tuple struct fields have no name to match, so each one must `rename` to its protobuf field.
+  /// A `flatten` field takes the rest of the message, so there can be only one.
+  pub fn validate(self) -> darling::Result<Self> {
+    let fields = self
+      .data
+      .as_ref()
+      .take_struct()
+      .expect("Should never be enum");
+    if self.message_type.is_none() {
+      if fields.style == ast::Style::Tuple && fields.fields.len() == 1 {
+        let field = &fields.fields[0];
+        return match field.first_attr() {
+          Some(attr) => Err(
+            darling::Error::custom(format!(
+              "`{}` has no effect on a newtype without `message_type`",
+              attr
+            ))
+            .with_span(&field.ty),
+          ),
+          None => Ok(self),
+        };
+      }
+      return Err(darling::Error::missing_field("message_type").with_span(&self.ident));
+    }
+    let mut errors: Vec<_> = fields
+      .iter()
+      .filter(|f| !f.skip)
+      .filter_map(|f| {
+        if f.flatten {
+          f.flatten_conflict().map(|attr| {
+            darling::Error::custom(format!("`flatten` can't be combined with `{}`", attr))
+              .with_span(&f.ty)
+          })
+        } else if let Some((first, second)) = f.converter_conflict() {
+          Some(
+            darling::Error::custom(format!("`{}` can't be combined with `{}`", first, second))
+              .with_span(&f.ty),
+          )
+        } else if f.map_fn.is_some() && f.default.is_some() && !f.skip_unpack {
+          Some(
+            darling::Error::custom("`map_fn` can't be combined with `default`").with_span(&f.ty),
+          )
+        } else if f.ident.is_none() && f.rename.is_none() && f.from.is_none() {
+          Some(darling::Error::custom("tuple struct field must have `rename`").with_span(&f.ty))
+        } else {
+          self.proto_path(f).err()
+        }
+      })
+      .collect();
+    errors.extend(fields.iter().filter_map(|f| f.pattern_error()));
+    let flattened: Vec<_> = fields.iter().filter(|f| f.flatten).collect();
+    if flattened.len() > 1 {
+      errors.push(
+        darling::Error::custom("only one field can be `flatten`").with_span(&flattened[1].ty),
+      );
+    }
+    if !flattened.is_empty() {
+      if self.pack_defaults {
+        errors.push(
+          darling::Error::custom("`flatten` can't be combined with `pack_defaults`")
+            .with_span(&self.ident),
+        );
+      }
+      if self.exhaustive {
+        errors.push(
+          darling::Error::custom("`flatten` can't be combined with `exhaustive`")
+            .with_span(&self.ident),
+        );
+      }
+    }
+    if self.exhaustive && self.pack_defaults {
+      errors.push(
+        darling::Error::custom("`exhaustive` can't be combined with `pack_defaults`")
+          .with_span(&self.ident),
+      );
+    }
+    if let Some(ref ignored) = self.ignore_proto_fields {
+      if !self.exhaustive {
+        errors.push(
+          darling::Error::custom("`ignore_proto_fields` requires `exhaustive`")
+            .with_span(&self.ident),
+        );
+      }
+      errors.extend(
+        ignored
+          .paths
+          .iter()
+          .filter(|path| path.get_ident().is_none())
+          .map(|path| darling::Error::custom("not a field name").with_span(path)),
+      );
+    }
+    if errors.is_empty() {
+      Ok(self)
+    } else {
+      Err(darling::Error::multiple(errors))
+    }
+  }
+
+  /// `ignore_proto_fields`, without those a struct field maps anyway.
+  fn ignored_proto_fields(&self) -> Vec<&syn::Ident> {
+    let mapped = self.proto_heads(|f| !f.skip);
+    self.ignore_proto_fields.as_ref().map_or(vec![], |ignored| {
+      ignored
+        .paths
+        .iter()
+        .map(|path| path.get_ident().expect("Checked by validate"))
+        .filter(|ident| !mapped.contains(ident))
+        .collect()
+    })
+  }
+
+  /// Message fields that only `skip_pack` fields map. An `exhaustive` struct fills them with
+  /// `Default::default()` on pack, as it can't use `pack_defaults`.
+  fn skip_pack_defaults(&self) -> Vec<syn::Ident> {
+    if !self.exhaustive {
+      return vec![];
+    }
+    let packed = self.proto_heads(|f| !f.skip && !f.skip_pack);
+    self
+      .proto_heads(|f| !f.skip && f.skip_pack)
+      .into_iter()
+      .filter(|ident| !packed.contains(ident))
+      .collect()
+  }
+
+  /// The first protobuf field on the path of each struct field that `keep` selects, without
+  /// duplicates.
+  fn proto_heads(&self, keep: impl Fn(&FieldReceiver) -> bool) -> Vec<syn::Ident> {
+    let fields = self
+      .data
+      .as_ref()
+      .take_struct()
+      .expect("Should never be enum")
+      .fields;
+    let mut heads = vec![];
+    for f in fields.into_iter().filter(|f| !f.flatten && keep(f)) {
+      let head = self.proto_path(f).expect("Checked by validate").remove(0);
+      if !heads.contains(&head) {
+        heads.push(head);
+      }
+    }
+    heads
+  }
+
+  /// With `exhaustive`, destructures the message without `..`, so that rustc rejects message
+  /// fields that neither a struct field nor `ignore_proto_fields` names. The error points at the
+  /// struct.
+  fn exhaustive_check(&self, message_type: &syn::Path) -> TokenStream {
+    if !self.exhaustive {
+      return quote! {};
+    }
+    let proto_idents = self.proto_heads(|f| !f.skip);
+    let ignored = self.ignored_proto_fields();
+    let message_type = self.spanned_message_type(message_type);
+    quote_spanned! { self.ident.span() =>
+      let #message_type {
+        #(#proto_idents: _,)*
+        #(#ignored: _,)*
+      } = &value;
+    }
+  }
+
+  /// `message_type` with the span of the struct, so that rustc's errors about its fields point
+  /// there rather than at the derive.
+  fn spanned_message_type(&self, message_type: &syn::Path) -> TokenStream {
+    let span = self.ident.span();
+    message_type
+      .to_token_stream()
+      .into_iter()
+      .map(|mut token| {
+        token.set_span(span);
+        token
+      })
+      .collect()
+  }
+
+  /// Path of the protobuf field of `f`, through nested messages for `from = "a.b"`.
+  fn proto_path(&self, f: &FieldReceiver) -> darling::Result<Vec<syn::Ident>> {
+    match f.parsed_from()? {
+      Some(path) => Ok(path),
+      None => self.proto_ident(f).map(|ident| vec![ident]),
+    }
+  }
+
+  /// Reads the protobuf field of `f` out of `value`. For `from = "a.b"` that's `__source`, bound
+  /// by `nested_source`. With a `flatten` field, the fields are taken rather than moved so that
+  /// the rest of the message can be unpacked after them.
+  fn unpack_source(&self, f: &FieldReceiver) -> TokenStream {
+    let path = self.proto_path(f).expect("Checked by validate");
+    match path.as_slice() {
+      [first] if self.has_flatten() => quote! { std::mem::take(&mut value.#first) },
+      [first] => quote! { value.#first },
+      _ => quote! { __source },
+    }
+  }
+
+  /// For `from = "a.b"`, the protobuf field of `f` if all the nested messages on the way are
+  /// present.
+  fn nested_source(&self, f: &FieldReceiver) -> Option<TokenStream> {
+    let path = self.proto_path(f).expect("Checked by validate");
+    let (first, rest) = path.split_first().expect("Should never be empty");
+    let (last, middle) = rest.split_last()?;
+    Some(quote! {
+      value.#first.as_mut()
+        #(.and_then(|m| m.#middle.as_mut()))*
+        .map(|m| std::mem::take(&mut m.#last))
+    })
+  }
+
+  /// Place of the protobuf field of `f` in `packed` for `from = "a.b"`, creating the nested
+  /// messages on the way.
+  fn pack_target(path: &[syn::Ident]) -> TokenStream {
+    let (last, init) = path.split_last().expect("Should never be empty");
+    quote! {
+      packed #(.#init.get_or_insert_with(Default::default))* .#last
+    }
+  }
+
+  fn has_flatten(&self) -> bool {
+    self
+      .data
+      .as_ref()
+      .take_struct()
+      .expect("Should never be enum")
+      .iter()
+      .any(|f| f.flatten)
+  }
+
+  fn needs_mut_value(&self) -> bool {
+    self.has_flatten()
+      || self
+        .data
+        .as_ref()
+        .take_struct()
+        .expect("Should never be enum")
+        .iter()
+        .any(|f| !f.skip && f.from.as_ref().is_some_and(|from| from.contains('.')))
+  }
+
+  /// Whether a `proto_enum` field converts the raw `i32` values rather than using prost's
+  /// setter and getter. Nested fields have neither.
+  fn is_raw_enum(&self, f: &FieldReceiver) -> bool {
+    f.from.is_some() || f.strict.unwrap_or(self.strict_enums)
+  }
+
+  /// The protobuf field of `f`. `validate` guarantees tuple struct fields have `rename`.
+  fn proto_ident(&self, f: &FieldReceiver) -> darling::Result<syn::Ident> {
+    if let Some(ref ident) = f.rename {
+      return Ok(ident.clone());
+    }
+    rename_ident(
+      f.ident.as_ref().expect("field ident"),
+      self.rename_all,
+      self.field_prefix.as_deref(),
+      self.field_suffix.as_deref(),
+    )
+  }
 }
 
 impl ToTokens for InputReceiver {
@@ -44,6 +323,9 @@ impl ToTokens for InputReceiver {
       ref generics,
       ref data,
       ref message_type,
+      pack_defaults,
+      ref validate_fn,
+      ..
     } = *self;
 
     let (imp, ty, wher) = generics.split_for_impl();
@@ -53,53 +335,159 @@ impl ToTokens for InputReceiver {
       .expect("Should never be enum")
       .fields;
 
+    let message_type = if let Some(message_type) = message_type.as_ref() {
+      message_type
+    } else {
+      self.transparent_to_tokens(fields[0], tokens);
+      return;
+    };
+
     match input_type {
       InputType::Pack => {
         let mut setter_lines: Vec<_> = vec![];
+        let mut ref_setter_lines: Vec<_> = vec![];
+        let mut nested_idents: Vec<syn::Ident> = vec![];
 
-        let pack_lines: Vec<_> = fields
+        let (pack_lines, ref_pack_lines): (Vec<_>, Vec<_>) = fields
           .iter()
-          .filter(|f| !f.skip_pack)
-          .map(|f| {
-            let field_ident = f.ident.as_ref().expect("field ident");
+          .enumerate()
+          .filter(|(_, f)| !f.skip && !f.skip_pack && !f.flatten)
+          .map(|(i, f)| {
+            let field_ident = f.member(i);
+            let field_name = f.path_name();
             let field_ty = &f.ty;
-            let value_field_ident = if let Some(ident) = f.rename.as_ref() {
-              ident
+            let path = self.proto_path(f).expect("Checked by validate");
+            let (value_expr, ref_value_expr) = if let Some(map_fn) = f.map_fn.as_ref() {
+              (
+                quote! { #map_fn(value.#field_ident) },
+                quote! { #map_fn(value.#field_ident.clone()) },
+              )
+            } else if let Some(pack_fn) = f.pack_fn() {
+              let map_err = quote! {
+                .map_err(|err| protobuf_mapper::result::Error::field(#field_name, err))?
+              };
+              (
+                quote! { #pack_fn(value.#field_ident)#map_err },
+                quote! { #pack_fn(value.#field_ident.clone())#map_err },
+              )
+            } else if f.proto_enum && self.is_raw_enum(f) {
+              let proto_enum_type = f.proto_enum_type();
+              (
+                quote! {
+                  <#field_ty as protobuf_mapper::proto_enum::EnumField<#proto_enum_type, _>>::pack(value.#field_ident)
+                    .map_err(|err| protobuf_mapper::result::Error::field(#field_name, err))?
+                },
+                quote! {
+                  <#field_ty as protobuf_mapper::proto_enum::EnumField<#proto_enum_type, _>>::pack_ref(&value.#field_ident)
+                    .map_err(|err| protobuf_mapper::result::Error::field(#field_name, err))?
+                },
+              )
+            } else if f.proto_enum {
+              let proto_ident = self.proto_ident(f).expect("Checked by validate");
+              let seter_ident = format_ident!("set_{}", proto_ident.unraw());
+              setter_lines.push(quote! {
+                packed.#seter_ident(
+                  <#field_ty as protobuf_mapper::ProtoEnum<_>>::try_into_proto_enum(value.#field_ident)
+                    .map_err(|err| protobuf_mapper::result::Error::field(#field_name, err))?
+                );
+              });
+              ref_setter_lines.push(quote! {
+                packed.#seter_ident(
+                  <#field_ty as protobuf_mapper::ProtoEnum<_>>::try_to_proto_enum(&value.#field_ident)
+                    .map_err(|err| protobuf_mapper::result::Error::field(#field_name, err))?
+                );
+              });
+              (quote! { Default::default() }, quote! { Default::default() })
+            } else if f.map_last_wins {
+              (
+                quote! { protobuf_mapper::map_last_wins::pack(value.#field_ident)? },
+                quote! { protobuf_mapper::map_last_wins::pack_ref(&value.#field_ident)? },
+              )
             } else {
-              f.ident.as_ref().unwrap()
+              (
+                quote! { value.#field_ident.pack()? },
+                quote! { protobuf_mapper::ProtoPackRef::pack_ref(&value.#field_ident)? },
+              )
             };
-            if let Some(map_fn) = f.map_fn.as_ref() {
-              quote! {
-                #value_field_ident: #map_fn(value.#field_ident),
-              }
+            if let [ref value_field_ident] = *path {
+              (
+                quote! {
+                  #value_field_ident: #value_expr,
+                },
+                quote! {
+                  #value_field_ident: #ref_value_expr,
+                },
+              )
             } else {
-              let value_expr = if f.proto_enum {
-                let seter_ident = syn::Ident::new(&format!("set_{}", field_ident.to_string()) as &str, Span::call_site());
-                setter_lines.push(quote! {
-                  packed.#seter_ident(
-                    <#field_ty as protobuf_mapper::ProtoEnum<_>>::into_proto_enum(value.#field_ident)
-                  );
-                });
-                quote! { Default::default() }
+              let target = Self::pack_target(&path);
+              setter_lines.push(quote! { #target = #value_expr; });
+              ref_setter_lines.push(quote! { #target = #ref_value_expr; });
+              let nested = &path[0];
+              if nested_idents.contains(nested) {
+                (quote! {}, quote! {})
               } else {
-                quote! { value.#field_ident.pack()? }
-              };
-              quote! {
-                #value_field_ident: #value_expr,
+                nested_idents.push(nested.clone());
+                let line = quote! { #nested: Default::default(), };
+                (line.clone(), line)
               }
             }
           })
-          .collect();
+          .unzip();
+        let flatten = fields.iter().enumerate().find(|(_, f)| f.flatten);
+        let ignored = self.ignored_proto_fields();
+        let skip_pack_defaults = self.skip_pack_defaults();
         for message_type in &message_type.paths {
+          let (rest, ref_rest) = if let Some((i, f)) = flatten {
+            let field_ident = f.member(i);
+            let field_ty = &f.ty;
+            (
+              quote! {
+                ..<#field_ty as protobuf_mapper::ProtoPack<#message_type>>::pack(value.#field_ident)?
+              },
+              quote! {
+                ..<#field_ty as protobuf_mapper::ProtoPackRef<#message_type>>::pack_ref(&value.#field_ident)?
+              },
+            )
+          } else if pack_defaults {
+            (
+              quote! { ..Default::default() },
+              quote! { ..Default::default() },
+            )
+          } else {
+            let defaults = quote! {
+              #(#skip_pack_defaults: Default::default(),)*
+              #(#ignored: Default::default(),)*
+            };
+            (defaults.clone(), defaults)
+          };
+          // The struct literal names every message field an `exhaustive` struct maps.
+          let literal_type = if self.exhaustive {
+            self.spanned_message_type(message_type)
+          } else {
+            quote! { #message_type }
+          };
           let pack_block = quote! {
             {
-              let mut packed = #message_type {
+              #[allow(clippy::needless_update)]
+              let mut packed = #literal_type {
                 #(#pack_lines)*
+                #rest
               };
               #(#setter_lines)*
               packed
             }
           };
+          let ref_pack_block = quote! {
+            {
+              #[allow(clippy::needless_update)]
+              let mut packed = #literal_type {
+                #(#ref_pack_lines)*
+                #ref_rest
+              };
+              #(#ref_setter_lines)*
+              packed
+            }
+          };
           tokens.extend(quote! {
             impl #imp protobuf_mapper::ProtoPack<#message_type> for #ident #ty #wher {
               fn pack(self) -> protobuf_mapper::result::Result<#message_type> {
@@ -114,65 +502,362 @@ impl ToTokens for InputReceiver {
                 Ok(Some(#pack_block))
               }
             }
+
+            impl #imp protobuf_mapper::ProtoPack<Box<#message_type>> for #ident #ty #wher {
+              fn pack(self) -> protobuf_mapper::result::Result<Box<#message_type>> {
+                <Self as protobuf_mapper::ProtoPack<#message_type>>::pack(self).map(Box::new)
+              }
+            }
+
+            impl #imp protobuf_mapper::ProtoPack<Option<Box<#message_type>>> for #ident #ty #wher {
+              fn pack(self) -> protobuf_mapper::result::Result<Option<Box<#message_type>>> {
+                <Self as protobuf_mapper::ProtoPack<#message_type>>::pack(self).map(|v| Some(Box::new(v)))
+              }
+            }
+          });
+
+          if !self.pack_ref {
+            continue;
+          }
+
+          tokens.extend(quote! {
+            impl #imp protobuf_mapper::ProtoPackRef<#message_type> for #ident #ty #wher {
+              fn pack_ref(&self) -> protobuf_mapper::result::Result<#message_type> {
+                let value = self;
+                Ok(#ref_pack_block)
+              }
+            }
+
+            impl #imp protobuf_mapper::ProtoPackRef<Option<#message_type>> for #ident #ty #wher {
+              fn pack_ref(&self) -> protobuf_mapper::result::Result<Option<#message_type>> {
+                let value = self;
+                Ok(Some(#ref_pack_block))
+              }
+            }
+
+            impl #imp protobuf_mapper::ProtoPackRef<Box<#message_type>> for #ident #ty #wher {
+              fn pack_ref(&self) -> protobuf_mapper::result::Result<Box<#message_type>> {
+                <Self as protobuf_mapper::ProtoPackRef<#message_type>>::pack_ref(self).map(Box::new)
+              }
+            }
+
+            impl #imp protobuf_mapper::ProtoPackRef<Option<Box<#message_type>>> for #ident #ty #wher {
+              fn pack_ref(&self) -> protobuf_mapper::result::Result<Option<Box<#message_type>>> {
+                <Self as protobuf_mapper::ProtoPackRef<#message_type>>::pack_ref(self).map(|v| Some(Box::new(v)))
+              }
+            }
           })
         }
       }
       InputType::Unpack => {
         let mut getter_lines: Vec<_> = vec![];
-        let unpack_lines: Vec<_> = fields
+        let mut collect_lines: Vec<_> = vec![];
+        let mut collect_idents: Vec<_> = vec![];
+        // A `flatten` field unpacks the rest of the message, so it goes after the other fields.
+        let mut flatten_lines: Vec<_> = vec![];
+        let mut flatten_collect_lines: Vec<_> = vec![];
+        let mut unpack_lines: Vec<_> = fields
           .iter()
-          .map(|f| {
-            let field_ident = &f.ident;
+          .enumerate()
+          .map(|(i, f)| {
+            let field_ident = f.member(i);
+            let field_name = f.path_name();
             let field_ty = &f.ty;
-            let value_field_ident = if let Some(ident) = f.rename.as_ref() {
-              ident
-            } else {
-              f.ident.as_ref().unwrap()
+            let collect_start = collect_lines.len();
+            let collect_ident = match f.ident {
+              Some(ref ident) => format_ident!("__{}", ident),
+              None => format_ident!("__{}", i),
             };
-            let field_expr = if let Some(map_fn) = f.map_fn.as_ref() {
+            let field_expr = if f.skip || f.skip_unpack {
+              let default_expr = f.default_expr();
+              collect_lines.push(quote! {
+                let #collect_ident: Option<#field_ty> = Some(#default_expr);
+              });
+              default_expr
+            } else if f.flatten {
+              collect_lines.push(quote! {
+                let #collect_ident: Option<#field_ty> = match <#field_ty as protobuf_mapper::ProtoUnpack<_>>::unpack_all_errors(value) {
+                  Ok(v) => Some(v),
+                  Err(errs) => {
+                    errors.extend(errs.into_iter().map(|err| {
+                      protobuf_mapper::result::Error::field(#field_name, err)
+                    }));
+                    None
+                  }
+                };
+              });
+              quote! {
+                <#field_ty as protobuf_mapper::ProtoUnpack<_>>::unpack(value).map_err(|err| {
+                  protobuf_mapper::result::Error::field(#field_name, err)
+                })?
+              }
+            } else if let Some(map_fn) = f.map_fn.as_ref() {
+              let source = self.unpack_source(f);
+              collect_lines.push(quote! {
+                let #collect_ident = Some(#map_fn(#source));
+              });
+              quote! {
+                #map_fn(#source)
+              }
+            } else if let Some(unpack_fn) = f.unpack_fn() {
+              let source = self.unpack_source(f);
+              let unpack_call = if f.default.is_some() {
+                let default_expr = f.default_expr();
+                quote! {
+                  match #unpack_fn(#source) {
+                    Err(ref err) if err.is_value_not_present() => Ok(#default_expr),
+                    res => res,
+                  }
+                }
+              } else {
+                quote! { #unpack_fn(#source) }
+              };
+              collect_lines.push(quote! {
+                let #collect_ident: Option<#field_ty> = match #unpack_call {
+                  Ok(v) => Some(v),
+                  Err(err) => {
+                    errors.push(protobuf_mapper::result::Error::field(#field_name, err));
+                    None
+                  }
+                };
+              });
               quote! {
-                #map_fn(value.#value_field_ident)
+                #unpack_call.map_err(|err| {
+                  protobuf_mapper::result::Error::field(#field_name, err)
+                })?
               }
             } else {
-              if f.proto_enum {
+              if f.proto_enum && !self.is_raw_enum(f) {
+                let value_field_ident = self.proto_ident(f).expect("Checked by validate");
                 getter_lines.push(quote! {
-                  let #field_ident = <#field_ty as protobuf_mapper::ProtoEnum<_>>::unpack_enum(value.#value_field_ident());
+                  let #collect_ident = <#field_ty as protobuf_mapper::ProtoEnum<_>>::unpack_enum(value.#value_field_ident())
+                    .map_err(|err| protobuf_mapper::result::Error::field(#field_name, err));
+                });
+                collect_lines.push(quote! {
+                  let #collect_ident = match #collect_ident {
+                    Ok(v) => Some(v),
+                    Err(err) => {
+                      errors.push(err);
+                      None
+                    }
+                  };
                 });
                 quote! {
-                  #field_ident
+                  #collect_ident?
                 }
               } else {
-                quote! {
-                  ProtoUnpack::unpack(value.#value_field_ident).map_err(|err| {
-                    if let protobuf_mapper::result::Error::ValueNotPresent = err {
-                      protobuf_mapper::result::Error::FieldValueNotPresent {
-                        field_name: stringify!(#field_ident),
+                let source = self.unpack_source(f);
+                let (unpack_call, collect_call) = if f.map_last_wins {
+                  (
+                    quote! { protobuf_mapper::map_last_wins::unpack(#source) },
+                    quote! {
+                      protobuf_mapper::map_last_wins::unpack(#source)
+                        .map_err(protobuf_mapper::result::Errors::from)
+                    },
+                  )
+                } else if f.proto_enum {
+                  let proto_enum_type = f.proto_enum_type();
+                  (
+                    quote! {
+                      <#field_ty as protobuf_mapper::proto_enum::EnumField<#proto_enum_type, _>>::unpack(#source)
+                    },
+                    quote! {
+                      <#field_ty as protobuf_mapper::proto_enum::EnumField<#proto_enum_type, _>>::unpack_all_errors(#source)
+                    },
+                  )
+                } else {
+                  (
+                    quote! { ProtoUnpack::unpack(#source) },
+                    quote! { ProtoUnpack::unpack_all_errors(#source) },
+                  )
+                };
+                let (unpack_call, collect_call) = if f.default.is_some() {
+                  let default_expr = f.default_expr();
+                  (
+                    quote! {
+                      match #unpack_call {
+                        Err(ref err) if err.is_value_not_present() => Ok(#default_expr),
+                        res => res,
                       }
-                    } else {
-                      err
+                    },
+                    quote! {
+                      match #collect_call {
+                        Err(ref errs) if errs.is_value_not_present() => Ok(#default_expr),
+                        res => res,
+                      }
+                    },
+                  )
+                } else {
+                  (unpack_call, collect_call)
+                };
+                collect_lines.push(quote! {
+                  let #collect_ident: Option<#field_ty> = match #collect_call {
+                    Ok(v) => Some(v),
+                    Err(errs) => {
+                      errors.extend(errs.into_iter().map(|err| {
+                        protobuf_mapper::result::Error::field(#field_name, err)
+                      }));
+                      None
                     }
+                  };
+                });
+                quote! {
+                  #unpack_call.map_err(|err| {
+                    protobuf_mapper::result::Error::field(#field_name, err)
                   })?
                 }
               }
             };
-            quote! {
+            // An absent nested message is a missing value, unless the field has a `default`.
+            let field_expr = match self.nested_source(f) {
+              Some(nested_source) if !f.skip && !f.skip_unpack => {
+                let (absent_expr, absent_collect) = if f.default.is_some() {
+                  let default_expr = f.default_expr();
+                  (default_expr.clone(), quote! { Some(#default_expr) })
+                } else {
+                  (
+                    quote! {
+                      return Err(protobuf_mapper::result::Error::field(
+                        #field_name,
+                        protobuf_mapper::result::Error::ValueNotPresent,
+                      ))
+                    },
+                    quote! {
+                      {
+                        errors.push(protobuf_mapper::result::Error::field(
+                          #field_name,
+                          protobuf_mapper::result::Error::ValueNotPresent,
+                        ));
+                        None
+                      }
+                    },
+                  )
+                };
+                let lines: Vec<_> = collect_lines.drain(collect_start..).collect();
+                collect_lines.push(quote! {
+                  let #collect_ident: Option<#field_ty> = match #nested_source {
+                    Some(__source) => {
+                      #(#lines)*
+                      #collect_ident
+                    }
+                    None => #absent_collect,
+                  };
+                });
+                quote! {
+                  match #nested_source {
+                    Some(__source) => #field_expr,
+                    None => #absent_expr,
+                  }
+                }
+              }
+              _ => field_expr,
+            };
+            let field_expr = if let Some(validate_expr) = f.checks_expr(&collect_ident) {
+              collect_lines.push(quote! {
+                let #collect_ident = match #collect_ident {
+                  Some(#collect_ident) => match #validate_expr {
+                    Ok(()) => Some(#collect_ident),
+                    Err(err) => {
+                      errors.push(protobuf_mapper::result::Error::field(#field_name, err));
+                      None
+                    }
+                  },
+                  None => None,
+                };
+              });
+              quote! {
+                {
+                  let #collect_ident = #field_expr;
+                  #validate_expr.map_err(|err| protobuf_mapper::result::Error::field(#field_name, err))?;
+                  #collect_ident
+                }
+              }
+            } else {
+              field_expr
+            };
+            collect_idents.push((field_ident.clone(), collect_ident));
+            let line = quote! {
               #field_ident: #field_expr,
+            };
+            if f.flatten {
+              flatten_collect_lines.extend(collect_lines.drain(collect_start..));
+              flatten_lines.push(line);
+              quote! {}
+            } else {
+              line
             }
           })
           .collect();
+        unpack_lines.extend(flatten_lines);
+        collect_lines.extend(flatten_collect_lines);
+        let mut_value_line = if self.needs_mut_value() {
+          quote! { let mut value = value; }
+        } else {
+          quote! {}
+        };
+
+        let (validate_line, collect_validate_line) = if let Some(validate_fn) = validate_fn.as_ref()
+        {
+          let validate_expr = validate_expr(validate_fn, quote! { &unpacked });
+          (
+            quote! { #validate_expr?; },
+            quote! { #validate_expr.map_err(protobuf_mapper::result::Errors::from)?; },
+          )
+        } else {
+          (quote! {}, quote! {})
+        };
+
+        let collect_result = if collect_idents.is_empty() {
+          quote! {
+            let unpacked = #ident {};
+            #collect_validate_line
+            Ok(unpacked)
+          }
+        } else {
+          let (field_idents, collect_idents): (Vec<_>, Vec<_>) = collect_idents.into_iter().unzip();
+          quote! {
+            match (#(#collect_idents,)*) {
+              (#(Some(#collect_idents),)*) => {
+                let unpacked = #ident {
+                  #(#field_idents: #collect_idents,)*
+                };
+                #collect_validate_line
+                Ok(unpacked)
+              }
+              _ => Err(protobuf_mapper::result::Errors::from(errors)),
+            }
+          }
+        };
 
         for message_type in &message_type.paths {
+          let exhaustive_check = self.exhaustive_check(message_type);
           let unpack_block = quote! {
+            #mut_value_line
+            #exhaustive_check
             #(#getter_lines)*
-            Ok(#ident {
+            let unpacked = #ident {
               #(#unpack_lines)*
-            })
+            };
+            #validate_line
+            Ok(unpacked)
+          };
+          let collect_block = quote! {
+            #mut_value_line
+            #exhaustive_check
+            #(#getter_lines)*
+            let mut errors: Vec<protobuf_mapper::result::Error> = vec![];
+            #(#collect_lines)*
+            #collect_result
           };
           tokens.extend(quote! {
             impl #imp protobuf_mapper::ProtoUnpack<#message_type> for #ident #ty #wher {
               fn unpack(value: #message_type) -> protobuf_mapper::result::Result<#ident> {
                 #unpack_block
               }
+
+              fn unpack_all_errors(value: #message_type) -> protobuf_mapper::result::Result<#ident, protobuf_mapper::result::Errors> {
+                #collect_block
+              }
             }
 
             impl #imp protobuf_mapper::ProtoUnpack<Option<#message_type>> for #ident #ty #wher {
@@ -183,6 +868,34 @@ impl ToTokens for InputReceiver {
                   Err(protobuf_mapper::result::Error::ValueNotPresent)
                 }
               }
+
+              fn unpack_all_errors(value: Option<#message_type>) -> protobuf_mapper::result::Result<#ident, protobuf_mapper::result::Errors> {
+                if let Some(value) = value {
+                  #collect_block
+                } else {
+                  Err(protobuf_mapper::result::Error::ValueNotPresent.into())
+                }
+              }
+            }
+
+            impl #imp protobuf_mapper::ProtoUnpack<Box<#message_type>> for #ident #ty #wher {
+              fn unpack(value: Box<#message_type>) -> protobuf_mapper::result::Result<#ident> {
+                <Self as protobuf_mapper::ProtoUnpack<#message_type>>::unpack(*value)
+              }
+
+              fn unpack_all_errors(value: Box<#message_type>) -> protobuf_mapper::result::Result<#ident, protobuf_mapper::result::Errors> {
+                <Self as protobuf_mapper::ProtoUnpack<#message_type>>::unpack_all_errors(*value)
+              }
+            }
+
+            impl #imp protobuf_mapper::ProtoUnpack<Option<Box<#message_type>>> for #ident #ty #wher {
+              fn unpack(value: Option<Box<#message_type>>) -> protobuf_mapper::result::Result<#ident> {
+                <Self as protobuf_mapper::ProtoUnpack<Option<#message_type>>>::unpack(value.map(|v| *v))
+              }
+
+              fn unpack_all_errors(value: Option<Box<#message_type>>) -> protobuf_mapper::result::Result<#ident, protobuf_mapper::result::Errors> {
+                <Self as protobuf_mapper::ProtoUnpack<Option<#message_type>>>::unpack_all_errors(value.map(|v| *v))
+              }
             }
           })
         }
@@ -191,6 +904,88 @@ impl ToTokens for InputReceiver {
   }
 }
 
+impl InputReceiver {
+  /// A newtype without `message_type` packs and unpacks as its only field.
+  fn transparent_to_tokens(&self, field: &FieldReceiver, tokens: &mut TokenStream) {
+    let ident = &self.ident;
+    let field_ty = &field.ty;
+    let (_, ty, _) = self.generics.split_for_impl();
+    let mut generics = self.generics.clone();
+    generics.params.push(syn::parse_quote!(__T));
+    let (imp, _, _) = generics.split_for_impl();
+    let where_clause = |bound: TokenStream| {
+      let mut where_clause = generics.clone().make_where_clause().clone();
+      where_clause
+        .predicates
+        .push(syn::parse_quote!(#field_ty: #bound));
+      where_clause
+    };
+
+    match self.input_type {
+      InputType::Pack => {
+        let pack_wher = where_clause(quote! { protobuf_mapper::ProtoPack<__T> });
+        tokens.extend(quote! {
+          impl #imp protobuf_mapper::ProtoPack<__T> for #ident #ty #pack_wher {
+            fn pack(self) -> protobuf_mapper::result::Result<__T> {
+              protobuf_mapper::ProtoPack::pack(self.0)
+            }
+          }
+        });
+        if !self.pack_ref {
+          return;
+        }
+        let ref_wher = where_clause(quote! { protobuf_mapper::ProtoPackRef<__T> });
+        tokens.extend(quote! {
+          impl #imp protobuf_mapper::ProtoPackRef<__T> for #ident #ty #ref_wher {
+            fn pack_ref(&self) -> protobuf_mapper::result::Result<__T> {
+              protobuf_mapper::ProtoPackRef::pack_ref(&self.0)
+            }
+          }
+        });
+      }
+      InputType::Unpack => {
+        let wher = where_clause(quote! { protobuf_mapper::ProtoUnpack<__T> });
+        let (validate_line, collect_validate_line) =
+          if let Some(validate_fn) = self.validate_fn.as_ref() {
+            let validate_expr = validate_expr(validate_fn, quote! { &unpacked });
+            (
+              quote! { #validate_expr?; },
+              quote! { #validate_expr.map_err(protobuf_mapper::result::Errors::from)?; },
+            )
+          } else {
+            (quote! {}, quote! {})
+          };
+        tokens.extend(quote! {
+          impl #imp protobuf_mapper::ProtoUnpack<__T> for #ident #ty #wher {
+            fn unpack(value: __T) -> protobuf_mapper::result::Result<Self> {
+              let unpacked = #ident(<#field_ty as protobuf_mapper::ProtoUnpack<__T>>::unpack(value)?);
+              #validate_line
+              Ok(unpacked)
+            }
+
+            fn unpack_all_errors(value: __T) -> protobuf_mapper::result::Result<Self, protobuf_mapper::result::Errors> {
+              let unpacked = #ident(<#field_ty as protobuf_mapper::ProtoUnpack<__T>>::unpack_all_errors(value)?);
+              #collect_validate_line
+              Ok(unpacked)
+            }
+          }
+        });
+      }
+    }
+  }
+}
+
+/// Calls a `fn(&T) -> Result<(), impl ToString>` validator, turning its message into
+/// `Error::Validation`. `Error::field` fills in the field name.
+fn validate_expr(validate_fn: &syn::Path, value: TokenStream) -> TokenStream {
+  quote! {
+    #validate_fn(#value).map_err(|message| protobuf_mapper::result::Error::Validation {
+      field: None,
+      message: ToString::to_string(&message),
+    })
+  }
+}
+
 #[derive(Debug, FromField)]
 #[darling(attributes(protobuf_mapper))]
 struct FieldReceiver {
@@ -198,10 +993,267 @@ struct FieldReceiver {
   ty: syn::Type,
   #[darling(default)]
   rename: Option<syn::Ident>,
+  /// Dotted path of the protobuf field through nested messages, e.g. `address.city`.
+  #[darling(default)]
+  from: Option<String>,
+  /// Converts the field from the fields of the message itself that the other fields don't map.
+  #[darling(default)]
+  flatten: bool,
   #[darling(default)]
   map_fn: Option<syn::Path>,
   #[darling(default)]
   proto_enum: bool,
+  /// Picks the `ProtoEnum` impl for `proto_enum` containers when the enum has several.
+  #[darling(default)]
+  proto_enum_type: Option<syn::Path>,
+  /// Convert the raw `i32` of a `proto_enum` field instead of using prost's setter and getter,
+  /// which enums inside a `Vec`, map or `Option` don't have, and which turns unknown values into
+  /// the default variant. Defaults to the struct's `strict_enums`.
+  #[darling(default)]
+  strict: Option<bool>,
   #[darling(default)]
   skip_pack: bool,
+  #[darling(default)]
+  map_last_wins: bool,
+  /// Value used when the protobuf value is absent, or for `skip`/`skip_unpack` fields:
+  /// `Default::default()`, or the result of the given function.
+  #[darling(default)]
+  default: Option<Override<syn::Path>>,
+  #[darling(default)]
+  skip_unpack: bool,
+  /// Shorthand for `skip_pack` and `skip_unpack`.
+  #[darling(default)]
+  skip: bool,
+  #[darling(default)]
+  pack_with: Option<syn::Path>,
+  #[darling(default)]
+  unpack_with: Option<syn::Path>,
+  /// Module providing `pack` and `unpack`, like serde's `with`.
+  #[darling(default)]
+  with: Option<syn::Path>,
+  #[darling(default, rename = "validate")]
+  validate_fn: Option<syn::Path>,
+  #[darling(default)]
+  required: bool,
+  #[darling(default)]
+  non_empty: bool,
+  #[darling(default)]
+  min_len: Option<usize>,
+  #[darling(default)]
+  max_len: Option<usize>,
+  #[darling(default)]
+  range: Option<Range>,
+  /// Regex the field must match, requires the `regex` feature of `protobuf-mapper`.
+  #[darling(default)]
+  pattern: Option<syn::LitStr>,
+}
+
+/// `range(min = 0, max = 100)`; either bound can be omitted.
+#[derive(Debug, Default, FromMeta)]
+struct Range {
+  #[darling(default)]
+  min: Option<syn::Lit>,
+  #[darling(default)]
+  max: Option<syn::Lit>,
+}
+
+impl FieldReceiver {
+  /// Proto enum type of a raw `proto_enum` field, inferred unless `proto_enum_type` is set.
+  fn proto_enum_type(&self) -> TokenStream {
+    match self.proto_enum_type {
+      Some(ref path) => quote! { #path },
+      None => quote! { _ },
+    }
+  }
+
+  fn parsed_from(&self) -> darling::Result<Option<Vec<syn::Ident>>> {
+    self
+      .from
+      .as_ref()
+      .map(|from| {
+        from
+          .split('.')
+          .map(|segment| {
+            syn::parse_str::<syn::Ident>(segment).map_err(|_| {
+              darling::Error::custom(format!("`{}` is not a field path", from)).with_span(&self.ty)
+            })
+          })
+          .collect()
+      })
+      .transpose()
+  }
+
+  /// The first attribute that a `flatten` field can't have: those picking the protobuf field or
+  /// converting its value.
+  fn flatten_conflict(&self) -> Option<&'static str> {
+    [
+      ("rename", self.rename.is_some()),
+      ("from", self.from.is_some()),
+      ("map_fn", self.map_fn.is_some()),
+      ("proto_enum", self.proto_enum),
+      ("map_last_wins", self.map_last_wins),
+      ("default", self.default.is_some()),
+      ("skip", self.skip || self.skip_pack || self.skip_unpack),
+      (
+        "with",
+        self.with.is_some() || self.pack_with.is_some() || self.unpack_with.is_some(),
+      ),
+    ]
+    .iter()
+    .find(|(_, conflict)| *conflict)
+    .map(|(attr, _)| *attr)
+  }
+
+  /// The first attribute set on the field, which a transparent newtype would ignore. Checks on
+  /// its value belong on the newtype itself as `validate`.
+  fn first_attr(&self) -> Option<&'static str> {
+    [
+      ("rename", self.rename.is_some()),
+      ("from", self.from.is_some()),
+      ("flatten", self.flatten),
+      ("map_fn", self.map_fn.is_some()),
+      ("proto_enum", self.proto_enum),
+      ("proto_enum_type", self.proto_enum_type.is_some()),
+      ("strict", self.strict.is_some()),
+      ("map_last_wins", self.map_last_wins),
+      ("default", self.default.is_some()),
+      ("skip", self.skip || self.skip_pack || self.skip_unpack),
+      (
+        "with",
+        self.with.is_some() || self.pack_with.is_some() || self.unpack_with.is_some(),
+      ),
+      ("validate", self.validate_fn.is_some()),
+      ("required", self.required),
+      ("non_empty", self.non_empty),
+      ("min_len", self.min_len.is_some()),
+      ("max_len", self.max_len.is_some()),
+      ("range", self.range.is_some()),
+      ("pattern", self.pattern.is_some()),
+    ]
+    .iter()
+    .find(|(_, set)| *set)
+    .map(|(attr, _)| *attr)
+  }
+
+  /// The first two attributes that each convert the value their own way, only one of which
+  /// would take effect. `pack_with` and `unpack_with` are two halves of one conversion.
+  fn converter_conflict(&self) -> Option<(&'static str, &'static str)> {
+    let pack_with = if self.pack_with.is_some() {
+      "pack_with"
+    } else {
+      "unpack_with"
+    };
+    let attrs = [
+      ("map_fn", self.map_fn.is_some()),
+      ("with", self.with.is_some()),
+      (
+        pack_with,
+        self.pack_with.is_some() || self.unpack_with.is_some(),
+      ),
+      ("proto_enum", self.proto_enum),
+      ("map_last_wins", self.map_last_wins),
+    ];
+    let mut set = attrs.iter().filter(|(_, set)| *set).map(|(attr, _)| *attr);
+    match (set.next(), set.next()) {
+      (Some(first), Some(second)) => Some((first, second)),
+      _ => None,
+    }
+  }
+
+  /// Checks the syntax of `pattern` here rather than when the regex is first used.
+  fn pattern_error(&self) -> Option<darling::Error> {
+    let pattern = self.pattern.as_ref()?;
+    let err = regex_syntax::Parser::new().parse(&pattern.value()).err()?;
+    // The full message quotes the pattern over several lines; the span already points at it.
+    let message = match err {
+      regex_syntax::Error::Parse(ref err) => err.kind().to_string(),
+      regex_syntax::Error::Translate(ref err) => err.kind().to_string(),
+      ref err => err.to_string(),
+    };
+    Some(darling::Error::custom(format!("invalid `pattern`: {}", message)).with_span(pattern))
+  }
+
+  /// The built-in validators of the field followed by `validate`, chained into one
+  /// `Result<()>` expression on `&value`.
+  fn checks_expr(&self, value: &syn::Ident) -> Option<TokenStream> {
+    let mut checks = vec![];
+    if self.required {
+      checks.push(quote! { protobuf_mapper::validate::required(&#value) });
+    }
+    if self.non_empty {
+      checks.push(quote! { protobuf_mapper::validate::non_empty(&#value) });
+    }
+    if let Some(min_len) = self.min_len {
+      checks.push(quote! { protobuf_mapper::validate::min_len(&#value, #min_len) });
+    }
+    if let Some(max_len) = self.max_len {
+      checks.push(quote! { protobuf_mapper::validate::max_len(&#value, #max_len) });
+    }
+    if let Some(range) = self.range.as_ref() {
+      if let Some(min) = range.min.as_ref() {
+        checks.push(quote! { protobuf_mapper::validate::min(&#value, #min) });
+      }
+      if let Some(max) = range.max.as_ref() {
+        checks.push(quote! { protobuf_mapper::validate::max(&#value, #max) });
+      }
+    }
+    if let Some(pattern) = self.pattern.as_ref() {
+      checks.push(quote! {
+        {
+          static PATTERN: std::sync::OnceLock<protobuf_mapper::validate::Regex> =
+            std::sync::OnceLock::new();
+          let regex = PATTERN.get_or_init(|| {
+            protobuf_mapper::validate::Regex::new(#pattern).expect("Checked by validate")
+          });
+          protobuf_mapper::validate::pattern(&#value, regex)
+        }
+      });
+    }
+    if let Some(validate_fn) = self.validate_fn.as_ref() {
+      checks.push(validate_expr(validate_fn, quote! { &#value }));
+    }
+    let mut checks = checks.into_iter();
+    let first = checks.next()?;
+    Some(quote! { #first #(.and_then(|()| #checks))* })
+  }
+
+  /// The Rust side of the field: its ident, or its position in a tuple struct.
+  fn member(&self, index: usize) -> syn::Member {
+    match self.ident {
+      Some(ref ident) => syn::Member::Named(ident.clone()),
+      None => syn::Member::Unnamed(index.into()),
+    }
+  }
+
+  /// Name used in error paths: the Rust field, or the protobuf field for tuple structs.
+  fn path_name(&self) -> String {
+    match self.ident.as_ref().or(self.rename.as_ref()) {
+      Some(ident) => ident.unraw().to_string(),
+      None => self.from.clone().expect("field ident"),
+    }
+  }
+
+  fn pack_fn(&self) -> Option<syn::Path> {
+    self.pack_with.clone().or_else(|| self.with_fn("pack"))
+  }
+
+  fn unpack_fn(&self) -> Option<syn::Path> {
+    self.unpack_with.clone().or_else(|| self.with_fn("unpack"))
+  }
+
+  fn with_fn(&self, name: &str) -> Option<syn::Path> {
+    self.with.clone().map(|mut path| {
+      path
+        .segments
+        .push(syn::Ident::new(name, Span::call_site()).into());
+      path
+    })
+  }
+
+  fn default_expr(&self) -> TokenStream {
+    match self.default {
+      Some(Override::Explicit(ref default_fn)) => quote! { #default_fn() },
+      _ => quote! { Default::default() },
+    }
+  }
 }
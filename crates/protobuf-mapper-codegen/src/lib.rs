@@ -7,6 +7,7 @@ use syn::{parse_macro_input, DeriveInput};
 mod types;
 mod derive_struct;
 mod derive_enum;
+mod derive_oneof;
 
 macro_rules! try_parse {
   ($e:expr) => {
@@ -20,22 +21,35 @@ macro_rules! try_parse {
 #[proc_macro_derive(ProtoPack, attributes(protobuf_mapper))]
 pub fn derive_pack(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
   let input = parse_macro_input!(input as DeriveInput);
-  let receiver = try_parse!(derive_struct::InputReceiver::from_derive_input(&input));
+  if let syn::Data::Enum(_) = input.data {
+    let receiver = try_parse!(derive_oneof::InputReceiver::from_derive_input(&input)
+      .and_then(derive_oneof::InputReceiver::validate));
+    return TokenStream::from(quote!(#receiver));
+  }
+  let receiver = try_parse!(derive_struct::InputReceiver::from_derive_input(&input)
+    .and_then(derive_struct::InputReceiver::validate));
   TokenStream::from(quote!(#receiver))
 }
 
 #[proc_macro_derive(ProtoUnpack, attributes(protobuf_mapper))]
 pub fn derive_unpack(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
   let input = parse_macro_input!(input as DeriveInput);
-  let receiver = try_parse!(derive_struct::InputReceiver::from_derive_input(&input)).to_unpack();
+  if let syn::Data::Enum(_) = input.data {
+    let receiver = try_parse!(derive_oneof::InputReceiver::from_derive_input(&input)
+      .and_then(derive_oneof::InputReceiver::validate))
+    .into_unpack();
+    return TokenStream::from(quote!(#receiver));
+  }
+  let receiver = try_parse!(derive_struct::InputReceiver::from_derive_input(&input)
+    .and_then(derive_struct::InputReceiver::validate))
+  .into_unpack();
   TokenStream::from(quote!(#receiver))
 }
 
 #[proc_macro_derive(ProtoEnum, attributes(protobuf_mapper))]
 pub fn derive_enum(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
   let input = parse_macro_input!(input as DeriveInput);
-  let receiver = try_parse!(derive_enum::InputReceiver::from_derive_input(
-    &input
-  ));
+  let receiver = try_parse!(derive_enum::InputReceiver::from_derive_input(&input)
+    .and_then(derive_enum::InputReceiver::validate));
   TokenStream::from(quote!(#receiver))
 }
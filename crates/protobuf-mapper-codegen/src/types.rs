@@ -1,5 +1,13 @@
 use darling::{Error, FromMeta};
-use syn;
+use heck::{ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+use syn::ext::IdentExt;
+
+#[derive(Debug, Copy, Clone, Default)]
+pub enum InputType {
+  #[default]
+  Pack,
+  Unpack,
+}
 
 #[derive(Debug)]
 pub struct Paths {
@@ -24,3 +32,67 @@ impl FromMeta for Paths {
     Ok(Self { paths: vec![path] })
   }
 }
+
+/// Case convention of `rename_all`, spelled like serde's.
+#[derive(Debug, Copy, Clone)]
+pub enum RenameRule {
+  Lower,
+  Upper,
+  Pascal,
+  Camel,
+  Snake,
+  ScreamingSnake,
+}
+
+impl RenameRule {
+  pub fn apply(self, name: &str) -> String {
+    match self {
+      RenameRule::Lower => name.to_lowercase(),
+      RenameRule::Upper => name.to_uppercase(),
+      RenameRule::Pascal => name.to_upper_camel_case(),
+      RenameRule::Camel => name.to_lower_camel_case(),
+      RenameRule::Snake => name.to_snake_case(),
+      RenameRule::ScreamingSnake => name.to_shouty_snake_case(),
+    }
+  }
+}
+
+impl FromMeta for RenameRule {
+  fn from_string(value: &str) -> Result<Self, Error> {
+    match value {
+      "lowercase" => Ok(RenameRule::Lower),
+      "UPPERCASE" => Ok(RenameRule::Upper),
+      "PascalCase" => Ok(RenameRule::Pascal),
+      "camelCase" => Ok(RenameRule::Camel),
+      "snake_case" => Ok(RenameRule::Snake),
+      "SCREAMING_SNAKE_CASE" => Ok(RenameRule::ScreamingSnake),
+      _ => Err(Error::unknown_value(value)),
+    }
+  }
+}
+
+/// Applies `rename_all` and then wraps the result in `prefix` and `suffix`. A raw identifier like
+/// `r#type` is renamed without its `r#`, which comes back if the result is a keyword.
+pub fn rename_ident(
+  ident: &syn::Ident,
+  rule: Option<RenameRule>,
+  prefix: Option<&str>,
+  suffix: Option<&str>,
+) -> Result<syn::Ident, Error> {
+  if rule.is_none() && prefix.is_none() && suffix.is_none() {
+    return Ok(ident.clone());
+  }
+  let name = ident.unraw().to_string();
+  let name = match rule {
+    Some(rule) => rule.apply(&name),
+    None => name,
+  };
+  let name = format!("{}{}{}", prefix.unwrap_or(""), name, suffix.unwrap_or(""));
+  if syn::parse_str::<syn::Ident>(&name).is_ok() {
+    Ok(syn::Ident::new(&name, ident.span()))
+  } else if syn::parse_str::<syn::Ident>(&format!("r#{}", name)).is_ok() {
+    Ok(syn::Ident::new_raw(&name, ident.span()))
+  } else {
+    Err(Error::custom(format!("`{}` is not a valid identifier", name)).with_span(ident))
+  }
+}
@@ -1,8 +1,16 @@
+#![allow(clippy::upper_case_acronyms)]
+
 use prost_types::value::Kind;
 use prost_types::{Struct, Value};
-use protobuf_mapper::{ProtoEnum, ProtoEnumMeta, ProtoPack, ProtoUnpack};
+use protobuf_mapper::result::{FieldViolation, MapEntryPart, PathSegment};
+use protobuf_mapper::{
+  ProtoEnum, ProtoEnumMeta, ProtoEnumName, ProtoEnumVariants, ProtoPack, ProtoPackRef, ProtoUnpack,
+};
 use serde_json::{json, Value as JsonValue};
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Debug, PartialEq, Clone)]
 struct Message {
@@ -34,7 +42,7 @@ fn map_i32(v: i32) -> i32 {
 }
 
 #[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
-#[protobuf_mapper(message_type = "Message")]
+#[protobuf_mapper(message_type = "Message", pack_ref)]
 struct Model {
   #[protobuf_mapper(map_fn = "map_i32")]
   v1: i32,
@@ -60,7 +68,7 @@ struct Model2 {
 }
 
 #[derive(Debug, ProtoPack, ProtoUnpack, PartialEq, Clone)]
-#[protobuf_mapper(message_type = "NestedMessage")]
+#[protobuf_mapper(message_type = "NestedMessage", pack_ref)]
 struct NestedModel {
   v: i32,
 }
@@ -105,8 +113,9 @@ fn derive_enum() {
 
 #[test]
 fn derive_enum_field() {
-  #[derive(Debug, PartialEq)]
+  #[derive(Debug, PartialEq, Default)]
   enum EnumProto {
+    #[default]
     A = 0,
     BBBB = 1,
   }
@@ -121,19 +130,13 @@ fn derive_enum_field() {
     }
   }
 
-  impl Default for EnumProto {
-    fn default() -> Self {
-      EnumProto::A
-    }
-  }
-
   impl From<EnumProto> for i32 {
     fn from(v: EnumProto) -> Self {
       v as i32
     }
   }
 
-  #[derive(Debug, ProtoEnum, PartialEq)]
+  #[derive(Debug, ProtoEnum, PartialEq, Clone, Copy)]
   #[protobuf_mapper(proto_enum_type = "EnumProto")]
   enum EnumModel {
     A,
@@ -156,11 +159,17 @@ fn derive_enum_field() {
   }
 
   #[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
-  #[protobuf_mapper(message_type(Message))]
+  #[protobuf_mapper(message_type(Message), pack_ref)]
   struct Model {
     #[protobuf_mapper(proto_enum)]
     f: EnumModel,
   }
+
+  let msg: Message = Model { f: EnumModel::B }.pack().unwrap();
+  assert_eq!(msg.f, 1);
+  let msg: Message = Model { f: EnumModel::B }.pack_ref().unwrap();
+  assert_eq!(msg.f, 1);
+  assert_eq!(Model::unpack(msg).unwrap(), Model { f: EnumModel::B });
 }
 
 #[test]
@@ -270,6 +279,37 @@ fn derive() {
   assert_eq!(msg_, msg);
 }
 
+#[test]
+fn derive_pack_ref() {
+  let mut map = HashMap::new();
+  map.insert(1, NestedModel { v: 2 });
+
+  let model = Model {
+    v1: 1,
+    v2: "text".to_string(),
+    json: json!({
+      "v": [1_f64, "a"]
+    }),
+    json_optional: Some(json!(null)),
+    elements: vec![NestedModel { v: 111 }],
+    map,
+  };
+
+  let msg: Message = model.pack_ref().unwrap();
+  let msg_: Message = (&model).pack().unwrap();
+  assert_eq!(msg_, msg);
+  assert_eq!(Model::unpack(msg).unwrap(), model);
+
+  let model = OneofModel {
+    kind: KindModel::Label {
+      text: "text".to_string(),
+    },
+    optional_kind: Some(KindModel::Nested(NestedModel { v: 1 })),
+  };
+  let msg: OneofMessage = model.pack_ref().unwrap();
+  assert_eq!(OneofModel::unpack(msg).unwrap(), model);
+}
+
 #[test]
 fn derive_multi() {
   let mut map = HashMap::new();
@@ -369,4 +409,2295 @@ fn derive_err() {
     format!("{}", err),
     "Could not unpack field 'json' from null"
   )
-}
\ No newline at end of file
+}
+#[derive(Debug, PartialEq, Clone)]
+struct ItemMessage {
+  json: Option<Value>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct OrderMessage {
+  items: Vec<ItemMessage>,
+  item: Option<ItemMessage>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "ItemMessage")]
+struct ItemModel {
+  json: JsonValue,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "OrderMessage")]
+struct OrderModel {
+  items: Vec<ItemModel>,
+  item: Option<ItemModel>,
+}
+
+#[test]
+fn derive_err_path() {
+  let json = Value {
+    kind: Some(Kind::BoolValue(true)),
+  };
+
+  let msg = OrderMessage {
+    items: vec![
+      ItemMessage {
+        json: Some(json.clone()),
+      },
+      ItemMessage { json: None },
+    ],
+    item: None,
+  };
+  let err = OrderModel::unpack(msg).err().unwrap();
+  assert_eq!(
+    err.path(),
+    vec![
+      PathSegment::Field("items"),
+      PathSegment::Index(1),
+      PathSegment::Field("json")
+    ]
+  );
+  assert_eq!(
+    format!("{}", err),
+    "items[1].json: Could not unpack field 'json' from null"
+  );
+
+  let msg = OrderMessage {
+    items: vec![],
+    item: Some(ItemMessage { json: None }),
+  };
+  let err = OrderModel::unpack(msg).err().unwrap();
+  assert_eq!(
+    err.path(),
+    vec![PathSegment::Field("item"), PathSegment::Field("json")]
+  );
+  assert_eq!(
+    format!("{}", err),
+    "item.json: Could not unpack field 'json' from null"
+  );
+}
+
+#[test]
+fn derive_unpack_all_errors() {
+  let msg = OrderMessage {
+    items: vec![
+      ItemMessage { json: None },
+      ItemMessage {
+        json: Some(Value {
+          kind: Some(Kind::BoolValue(true)),
+        }),
+      },
+      ItemMessage { json: None },
+    ],
+    item: Some(ItemMessage { json: None }),
+  };
+
+  let errors = OrderModel::unpack_all_errors(msg).err().unwrap();
+  assert_eq!(
+    errors.field_violations(),
+    vec![
+      FieldViolation {
+        field: "items[0].json".to_string(),
+        description: "Could not unpack field 'json' from null".to_string(),
+      },
+      FieldViolation {
+        field: "items[2].json".to_string(),
+        description: "Could not unpack field 'json' from null".to_string(),
+      },
+      FieldViolation {
+        field: "item.json".to_string(),
+        description: "Could not unpack field 'json' from null".to_string(),
+      },
+    ]
+  );
+
+  let msg = OrderMessage {
+    items: vec![],
+    item: None,
+  };
+  assert_eq!(
+    OrderModel::unpack_all_errors(msg).unwrap(),
+    OrderModel {
+      items: vec![],
+      item: None,
+    }
+  );
+}
+
+/// `optional int32 presence = 1;` and `google.protobuf.Int32Value wrapper = 2;` are both
+/// generated as `Option<i32>` by prost.
+#[derive(Debug, PartialEq, Clone)]
+struct OptionalMessage {
+  presence: Option<i32>,
+  wrapper: Option<i32>,
+  required_presence: Option<String>,
+  required_wrapper: Option<String>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "OptionalMessage")]
+struct OptionalModel {
+  presence: Option<i32>,
+  wrapper: Option<i32>,
+  required_presence: String,
+  required_wrapper: String,
+}
+
+#[test]
+fn derive_optional_scalar() {
+  let msg = OptionalMessage {
+    presence: Some(1),
+    wrapper: None,
+    required_presence: Some("a".to_string()),
+    required_wrapper: Some("b".to_string()),
+  };
+
+  let model = OptionalModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    OptionalModel {
+      presence: Some(1),
+      wrapper: None,
+      required_presence: "a".to_string(),
+      required_wrapper: "b".to_string(),
+    }
+  );
+
+  let msg_: OptionalMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let err = OptionalModel::unpack(OptionalMessage {
+    required_presence: None,
+    ..msg
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack field 'required_presence' from null"
+  );
+}
+
+#[test]
+fn wrappers() {
+  use protobuf_mapper::wrappers::*;
+
+  macro_rules! assert_round_trip {
+    ($wrapper:ident, $ty:ty, $value:expr) => {{
+      let value: $ty = $value;
+      let packed: Option<$wrapper> = Some(value.clone()).pack().unwrap();
+      assert_eq!(
+        packed,
+        Some($wrapper {
+          value: value.clone()
+        })
+      );
+      assert_eq!(
+        Option::<$ty>::unpack(packed).unwrap(),
+        Some(value.clone())
+      );
+
+      let packed: Option<$wrapper> = None::<$ty>.pack().unwrap();
+      assert_eq!(packed, None);
+      assert_eq!(Option::<$ty>::unpack(packed).unwrap(), None);
+
+      let packed: $wrapper = value.clone().pack().unwrap();
+      assert_eq!(packed.value, value);
+      assert_eq!(<$ty>::unpack(packed).unwrap(), value);
+    }};
+  }
+
+  assert_round_trip!(FloatValue, f32, 1.5_f32);
+  assert_round_trip!(DoubleValue, f64, 1.5_f64);
+  assert_round_trip!(Int64Value, i64, -1_i64);
+  assert_round_trip!(UInt64Value, u64, 1_u64);
+  assert_round_trip!(Int32Value, i32, -1_i32);
+  assert_round_trip!(UInt32Value, u32, 1_u32);
+  assert_round_trip!(BoolValue, bool, true);
+  assert_round_trip!(StringValue, String, "text".to_string());
+  assert_round_trip!(BytesValue, Vec<u8>, vec![1_u8, 2, 3]);
+}
+
+#[test]
+fn timestamp() {
+  use chrono::{DateTime, Utc};
+  use prost_types::Timestamp;
+
+  let ts = Timestamp {
+    seconds: 1_600_000_000,
+    nanos: 5,
+  };
+  let dt = DateTime::<Utc>::unpack(ts.clone()).unwrap();
+  assert_eq!(ProtoPack::<Timestamp>::pack(dt).unwrap(), ts);
+
+  for (seconds, nanos) in [(0, -1), (0, 1_000_000_000), (i64::MAX, 0)] {
+    let err = DateTime::<Utc>::unpack(Timestamp { seconds, nanos })
+      .err()
+      .unwrap();
+    assert_eq!(
+      format!("{}", err),
+      format!(
+        "Timestamp is out of range: seconds = {}, nanos = {}",
+        seconds, nanos
+      )
+    );
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct WrapperMessage {
+  optional: Option<protobuf_mapper::wrappers::Int64Value>,
+  required: Option<protobuf_mapper::wrappers::StringValue>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "WrapperMessage")]
+struct WrapperModel {
+  optional: Option<i64>,
+  required: String,
+}
+
+#[test]
+fn derive_wrappers() {
+  use protobuf_mapper::wrappers::{Int64Value, StringValue};
+
+  let msg = WrapperMessage {
+    optional: Some(Int64Value { value: 1 }),
+    required: Some(StringValue {
+      value: "text".to_string(),
+    }),
+  };
+  let model = WrapperModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    WrapperModel {
+      optional: Some(1),
+      required: "text".to_string(),
+    }
+  );
+  let msg_: WrapperMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let err = WrapperModel::unpack(WrapperMessage {
+    optional: None,
+    required: None,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack field 'required' from null"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct CollectionMessage {
+  hash_to_btree: BTreeMap<i32, NestedMessage>,
+  btree_to_hash: HashMap<String, i32>,
+  btree_to_btree: BTreeMap<i32, NestedMessage>,
+  index_to_hash: HashMap<String, i32>,
+  hash_set: Vec<String>,
+  btree_set: Vec<i32>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "CollectionMessage", pack_ref)]
+struct CollectionModel {
+  hash_to_btree: HashMap<i32, NestedModel>,
+  btree_to_hash: BTreeMap<String, i32>,
+  btree_to_btree: BTreeMap<i32, NestedModel>,
+  index_to_hash: IndexMap<String, i32>,
+  hash_set: HashSet<String>,
+  btree_set: BTreeSet<i32>,
+}
+
+#[test]
+fn derive_collections() {
+  let msg = CollectionMessage {
+    hash_to_btree: vec![(1, NestedMessage { v: 1 })].into_iter().collect(),
+    btree_to_hash: vec![("a".to_string(), 1)].into_iter().collect(),
+    btree_to_btree: vec![(2, NestedMessage { v: 2 })].into_iter().collect(),
+    index_to_hash: vec![("b".to_string(), 2)].into_iter().collect(),
+    hash_set: vec!["a".to_string()],
+    btree_set: vec![1, 2, 3],
+  };
+
+  let model = CollectionModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    CollectionModel {
+      hash_to_btree: vec![(1, NestedModel { v: 1 })].into_iter().collect(),
+      btree_to_hash: vec![("a".to_string(), 1)].into_iter().collect(),
+      btree_to_btree: vec![(2, NestedModel { v: 2 })].into_iter().collect(),
+      index_to_hash: vec![("b".to_string(), 2)].into_iter().collect(),
+      hash_set: vec!["a".to_string()].into_iter().collect(),
+      btree_set: vec![1, 2, 3].into_iter().collect(),
+    }
+  );
+
+  let msg_: CollectionMessage = model.pack_ref().unwrap();
+  assert_eq!(msg_, msg);
+  let msg_: CollectionMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let err = CollectionModel::unpack(CollectionMessage {
+    btree_set: vec![1, 2, 1],
+    ..msg
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    err.path(),
+    vec![PathSegment::Field("btree_set"), PathSegment::Index(2)]
+  );
+  assert_eq!(format!("{}", err), "btree_set[2]: Duplicate set element");
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
+struct Lowercase(String);
+
+impl ProtoPack<String> for Lowercase {
+  fn pack(self) -> protobuf_mapper::result::Result<String> {
+    Ok(self.0.to_lowercase())
+  }
+}
+
+impl ProtoPackRef<String> for Lowercase {
+  fn pack_ref(&self) -> protobuf_mapper::result::Result<String> {
+    Ok(self.0.to_lowercase())
+  }
+}
+
+impl ProtoUnpack<String> for Lowercase {
+  fn unpack(value: String) -> protobuf_mapper::result::Result<Self> {
+    Ok(Lowercase(value.to_lowercase()))
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct KeyMessage {
+  map: HashMap<String, i32>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "KeyMessage", pack_ref)]
+struct KeyModel {
+  map: BTreeMap<Lowercase, i32>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "KeyMessage", pack_ref)]
+struct KeyLastWinsModel {
+  #[protobuf_mapper(map_last_wins)]
+  map: BTreeMap<Lowercase, i32>,
+}
+
+#[test]
+fn derive_duplicate_map_key() {
+  let map: BTreeMap<_, _> = vec![
+    (Lowercase("A".to_string()), 1),
+    (Lowercase("a".to_string()), 2),
+  ]
+  .into_iter()
+  .collect();
+
+  let err = <KeyModel as ProtoPack<KeyMessage>>::pack(KeyModel { map: map.clone() })
+    .err()
+    .unwrap();
+  assert_eq!(format!("{}", err), "Duplicate map key: \"a\"");
+  let err = <KeyModel as ProtoPackRef<KeyMessage>>::pack_ref(&KeyModel { map: map.clone() })
+    .err()
+    .unwrap();
+  assert_eq!(format!("{}", err), "Duplicate map key: \"a\"");
+
+  let msg: KeyMessage = KeyLastWinsModel { map: map.clone() }.pack().unwrap();
+  assert_eq!(msg.map, vec![("a".to_string(), 2)].into_iter().collect());
+  let msg_: KeyMessage = KeyLastWinsModel { map }.pack_ref().unwrap();
+  assert_eq!(msg_, msg);
+
+  let msg = KeyMessage {
+    map: vec![("A".to_string(), 1), ("a".to_string(), 1)]
+      .into_iter()
+      .collect(),
+  };
+  let err = KeyModel::unpack(msg.clone()).err().unwrap();
+  assert_eq!(err.path(), vec![PathSegment::Field("map")]);
+  assert_eq!(
+    format!("{}", err),
+    "map: Duplicate map key: Lowercase(\"a\")"
+  );
+
+  let model = KeyLastWinsModel::unpack(msg).unwrap();
+  assert_eq!(
+    model.map,
+    vec![(Lowercase("a".to_string()), 1)].into_iter().collect()
+  );
+}
+
+#[derive(Debug, PartialEq)]
+enum StatusProto {
+  Active = 0,
+}
+
+impl StatusProto {
+  fn from_i32(v: i32) -> Option<Self> {
+    match v {
+      0 => Some(StatusProto::Active),
+      _ => None,
+    }
+  }
+}
+
+impl From<StatusProto> for i32 {
+  fn from(v: StatusProto) -> Self {
+    v as i32
+  }
+}
+
+#[derive(Debug, ProtoEnum, PartialEq, Eq, Hash)]
+#[protobuf_mapper(proto_enum_type = "StatusProto")]
+enum StatusModel {
+  Active,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct MapEntryMessage {
+  items: HashMap<String, ItemMessage>,
+  statuses: HashMap<i32, i32>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "MapEntryMessage")]
+struct MapEntryModel {
+  items: HashMap<String, ItemModel>,
+  statuses: HashMap<StatusModel, i32>,
+}
+
+#[test]
+fn derive_map_entry_err() {
+  let msg = MapEntryMessage {
+    items: vec![("a".to_string(), ItemMessage { json: None })]
+      .into_iter()
+      .collect(),
+    statuses: HashMap::new(),
+  };
+  let err = MapEntryModel::unpack(msg).err().unwrap();
+  assert_eq!(
+    err.path(),
+    vec![
+      PathSegment::Field("items"),
+      PathSegment::MapEntry {
+        key: Some("\"a\"".to_string()),
+        part: MapEntryPart::Value,
+      },
+      PathSegment::Field("json"),
+    ]
+  );
+  assert_eq!(
+    format!("{}", err),
+    "items[\"a\"].json: Could not unpack field 'json' from null"
+  );
+
+  let msg = MapEntryMessage {
+    items: HashMap::new(),
+    statuses: vec![(0, 1), (7, 2)].into_iter().collect(),
+  };
+  let err = MapEntryModel::unpack(msg).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "statuses[key: 7]: Enum discriminant is not found: enum type = StatusModel, discriminant = 7"
+  );
+
+  let ports: HashMap<_, _> = vec![(Port(-1), 1)].into_iter().collect();
+  let err = ProtoPackRef::<HashMap<i32, i32>>::pack_ref(&ports)
+    .err()
+    .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "[key: Port(-1)]: Invalid value: must not be negative"
+  );
+  let err = ProtoPack::<HashMap<i32, i32>>::pack(ports).err().unwrap();
+  assert_eq!(
+    err.path(),
+    vec![PathSegment::MapEntry {
+      key: None,
+      part: MapEntryPart::Key,
+    }]
+  );
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Port(i32);
+
+impl ProtoPack<i32> for Port {
+  fn pack(self) -> protobuf_mapper::result::Result<i32> {
+    self.pack_ref()
+  }
+}
+
+impl ProtoPackRef<i32> for Port {
+  fn pack_ref(&self) -> protobuf_mapper::result::Result<i32> {
+    if self.0 < 0 {
+      return Err(protobuf_mapper::result::Error::Validation {
+        field: None,
+        message: "must not be negative".to_string(),
+      });
+    }
+    Ok(self.0)
+  }
+}
+
+mod oneof_message {
+  #[derive(Debug, PartialEq, Clone)]
+  pub enum Kind {
+    Number(i32),
+    Text(String),
+    Nested(super::NestedMessage),
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct OneofMessage {
+  kind: Option<oneof_message::Kind>,
+  optional_kind: Option<oneof_message::Kind>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(oneof_type = "oneof_message::Kind", pack_ref)]
+enum KindModel {
+  Number(i32),
+  #[protobuf_mapper(rename = "Text")]
+  Label {
+    text: String,
+  },
+  Nested(NestedModel),
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "OneofMessage", pack_ref)]
+struct OneofModel {
+  kind: KindModel,
+  optional_kind: Option<KindModel>,
+}
+
+#[test]
+fn derive_oneof() {
+  let msg = OneofMessage {
+    kind: Some(oneof_message::Kind::Text("text".to_string())),
+    optional_kind: Some(oneof_message::Kind::Nested(NestedMessage { v: 1 })),
+  };
+
+  let model = OneofModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    OneofModel {
+      kind: KindModel::Label {
+        text: "text".to_string()
+      },
+      optional_kind: Some(KindModel::Nested(NestedModel { v: 1 })),
+    }
+  );
+
+  let msg_: OneofMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let msg = OneofMessage {
+    kind: Some(oneof_message::Kind::Number(1)),
+    optional_kind: None,
+  };
+  let model = OneofModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    OneofModel {
+      kind: KindModel::Number(1),
+      optional_kind: None,
+    }
+  );
+  let msg_: OneofMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+}
+
+#[test]
+fn derive_oneof_err() {
+  let msg = OneofMessage {
+    kind: None,
+    optional_kind: None,
+  };
+
+  let err = OneofModel::unpack(msg).err().unwrap();
+  assert_eq!(err.path(), vec![PathSegment::Field("kind")]);
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack oneof field 'kind' from null"
+  );
+
+  // The error names the struct field, whatever the oneof type is called.
+  #[derive(Debug, ProtoPack, ProtoUnpack)]
+  #[protobuf_mapper(message_type = "OneofMessage")]
+  struct ChoiceModel {
+    #[protobuf_mapper(rename = "kind")]
+    choice: KindModel,
+    #[protobuf_mapper(rename = "optional_kind")]
+    _optional_choice: Option<KindModel>,
+  }
+
+  let err = ChoiceModel::unpack(OneofMessage {
+    kind: None,
+    optional_kind: None,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(err.path(), vec![PathSegment::Field("choice")]);
+  assert_eq!(
+    format!("{}", err),
+    "Could not unpack oneof field 'choice' from null"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct TreeMessage {
+  v: i32,
+  parent: Option<Box<TreeMessage>>,
+  children: Vec<TreeMessage>,
+  shared: Option<NestedMessage>,
+  tags: Vec<NestedMessage>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "TreeMessage", pack_ref)]
+struct TreeModel {
+  v: i32,
+  parent: Option<Box<TreeModel>>,
+  children: Vec<TreeModel>,
+  shared: Arc<NestedModel>,
+  tags: Vec<Rc<NestedModel>>,
+}
+
+#[test]
+fn derive_pointers() {
+  let msg = TreeMessage {
+    v: 1,
+    parent: Some(Box::new(TreeMessage {
+      v: 0,
+      parent: None,
+      children: vec![],
+      shared: Some(NestedMessage { v: 0 }),
+      tags: vec![],
+    })),
+    children: vec![TreeMessage {
+      v: 2,
+      parent: None,
+      children: vec![],
+      shared: Some(NestedMessage { v: 2 }),
+      tags: vec![NestedMessage { v: 3 }],
+    }],
+    shared: Some(NestedMessage { v: 1 }),
+    tags: vec![],
+  };
+
+  let model = TreeModel::unpack(msg.clone()).unwrap();
+  assert_eq!(model.parent.as_ref().unwrap().v, 0);
+  assert_eq!(*model.children[0].tags[0], NestedModel { v: 3 });
+  assert_eq!(*model.shared, NestedModel { v: 1 });
+
+  let msg_: TreeMessage = model.pack_ref().unwrap();
+  assert_eq!(msg_, msg);
+  let msg_: TreeMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let mut msg = msg;
+  msg.parent.as_mut().unwrap().shared = None;
+  let err = TreeModel::unpack(msg).err().unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "parent.shared: Could not unpack field 'shared' from null"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct DefaultMessage {
+  nested: Option<NestedMessage>,
+  nested_fn: Option<NestedMessage>,
+  count: Option<i32>,
+}
+
+fn default_nested() -> NestedModel {
+  NestedModel { v: 42 }
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "DefaultMessage")]
+struct DefaultModel {
+  #[protobuf_mapper(default)]
+  nested: NestedModel,
+  #[protobuf_mapper(default = "default_nested")]
+  nested_fn: NestedModel,
+  #[protobuf_mapper(default)]
+  count: i32,
+}
+
+impl Default for NestedModel {
+  fn default() -> Self {
+    NestedModel { v: -1 }
+  }
+}
+
+#[test]
+fn derive_field_default() {
+  let msg = DefaultMessage {
+    nested: None,
+    nested_fn: None,
+    count: None,
+  };
+  let expected = DefaultModel {
+    nested: NestedModel { v: -1 },
+    nested_fn: NestedModel { v: 42 },
+    count: 0,
+  };
+  assert_eq!(DefaultModel::unpack(msg.clone()).unwrap(), expected);
+  assert_eq!(DefaultModel::unpack_all_errors(msg).unwrap(), expected);
+
+  let msg = DefaultMessage {
+    nested: Some(NestedMessage { v: 1 }),
+    nested_fn: Some(NestedMessage { v: 2 }),
+    count: Some(3),
+  };
+  let model = DefaultModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    DefaultModel {
+      nested: NestedModel { v: 1 },
+      nested_fn: NestedModel { v: 2 },
+      count: 3,
+    }
+  );
+  let msg_: DefaultMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct SkipMessage {
+  v: i32,
+  proto_only: String,
+}
+
+fn default_label() -> String {
+  "label".to_string()
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "SkipMessage", pack_defaults, pack_ref)]
+struct SkipModel {
+  v: i32,
+  #[protobuf_mapper(skip)]
+  cache: Vec<i32>,
+  #[protobuf_mapper(skip_unpack, default = "default_label", rename = "proto_only")]
+  label: String,
+}
+
+#[test]
+fn derive_skip() {
+  let msg = SkipMessage {
+    v: 1,
+    proto_only: "ignored".to_string(),
+  };
+  let model = SkipModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    SkipModel {
+      v: 1,
+      cache: vec![],
+      label: "label".to_string(),
+    }
+  );
+  assert_eq!(SkipModel::unpack_all_errors(msg).unwrap(), model);
+
+  let model = SkipModel {
+    v: 2,
+    cache: vec![1, 2],
+    label: "packed".to_string(),
+  };
+  let msg: SkipMessage = model.pack_ref().unwrap();
+  assert_eq!(
+    msg,
+    SkipMessage {
+      v: 2,
+      proto_only: "packed".to_string(),
+    }
+  );
+
+  #[derive(Debug, ProtoPack, PartialEq)]
+  #[protobuf_mapper(message_type = "SkipMessage", pack_defaults)]
+  struct PartialModel {
+    v: i32,
+  }
+
+  let msg: SkipMessage = PartialModel { v: 3 }.pack().unwrap();
+  assert_eq!(
+    msg,
+    SkipMessage {
+      v: 3,
+      proto_only: String::new(),
+    }
+  );
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct WithMessage {
+  cents: i64,
+  code: String,
+}
+
+fn pack_cents(v: f64) -> protobuf_mapper::result::Result<i64> {
+  if v.is_finite() {
+    Ok((v * 100.0).round() as i64)
+  } else {
+    Err(protobuf_mapper::result::Error::Validation {
+      field: None,
+      message: "must be finite".to_string(),
+    })
+  }
+}
+
+fn unpack_cents(v: i64) -> protobuf_mapper::result::Result<f64> {
+  Ok(v as f64 / 100.0)
+}
+
+mod upper {
+  use protobuf_mapper::result::{Error, Result};
+
+  pub fn pack(v: String) -> Result<String> {
+    Ok(v.to_lowercase())
+  }
+
+  pub fn unpack(v: String) -> Result<String> {
+    if v.is_empty() {
+      Err(Error::ValueNotPresent)
+    } else {
+      Ok(v.to_uppercase())
+    }
+  }
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "WithMessage", pack_ref)]
+struct WithModel {
+  #[protobuf_mapper(
+    pack_with = "pack_cents",
+    unpack_with = "unpack_cents",
+    rename = "cents"
+  )]
+  amount: f64,
+  #[protobuf_mapper(with = "upper")]
+  code: String,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "WithMessage")]
+struct WithDefaultModel {
+  cents: i64,
+  #[protobuf_mapper(with = "upper", default = "default_code")]
+  code: String,
+}
+
+fn default_code() -> String {
+  "XXX".to_string()
+}
+
+#[test]
+fn derive_with() {
+  let msg = WithMessage {
+    cents: 1250,
+    code: "usd".to_string(),
+  };
+  let model = WithModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    WithModel {
+      amount: 12.5,
+      code: "USD".to_string(),
+    }
+  );
+  let msg_: WithMessage = model.pack_ref().unwrap();
+  assert_eq!(msg_, msg);
+  let msg_: WithMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let err = WithModel::unpack(WithMessage {
+    cents: 0,
+    code: String::new(),
+  })
+  .err()
+  .unwrap();
+  assert_eq!(err.path(), vec![PathSegment::Field("code")]);
+
+  let msg = WithMessage {
+    cents: 0,
+    code: String::new(),
+  };
+  let expected = WithDefaultModel {
+    cents: 0,
+    code: "XXX".to_string(),
+  };
+  assert_eq!(WithDefaultModel::unpack(msg.clone()).unwrap(), expected);
+  assert_eq!(WithDefaultModel::unpack_all_errors(msg).unwrap(), expected);
+
+  let err = ProtoPack::<WithMessage>::pack(WithModel {
+    amount: f64::NAN,
+    code: "usd".to_string(),
+  })
+  .err()
+  .unwrap();
+  assert_eq!(err.path(), vec![PathSegment::Field("amount")]);
+  assert_eq!(
+    format!("{}", err),
+    "Invalid value for field 'amount': must be finite"
+  );
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq, Clone)]
+#[protobuf_mapper(pack_ref)]
+struct OrderId(i64);
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq, Eq, Hash, Clone)]
+#[protobuf_mapper(pack_ref)]
+struct Sku(String);
+
+#[derive(Debug, PartialEq, Clone)]
+struct NewtypeMessage {
+  id: i64,
+  parent_id: Option<i64>,
+  skus: Vec<String>,
+  quantities: HashMap<String, i32>,
+  nested: Option<NestedMessage>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(pack_ref)]
+struct Nested(NestedModel);
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "NewtypeMessage", pack_ref)]
+struct NewtypeModel {
+  id: OrderId,
+  parent_id: Option<OrderId>,
+  skus: Vec<Sku>,
+  quantities: HashMap<Sku, i32>,
+  nested: Nested,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "DefaultMessage")]
+struct DefaultTuple(
+  #[protobuf_mapper(rename = "nested")] NestedModel,
+  #[protobuf_mapper(rename = "nested_fn")] NestedModel,
+  #[protobuf_mapper(rename = "count")] i32,
+);
+
+#[test]
+fn derive_tuple_struct() {
+  let msg = NewtypeMessage {
+    id: 1,
+    parent_id: None,
+    skus: vec!["a".to_string()],
+    quantities: vec![("a".to_string(), 2)].into_iter().collect(),
+    nested: Some(NestedMessage { v: 3 }),
+  };
+  let model = NewtypeModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    NewtypeModel {
+      id: OrderId(1),
+      parent_id: None,
+      skus: vec![Sku("a".to_string())],
+      quantities: vec![(Sku("a".to_string()), 2)].into_iter().collect(),
+      nested: Nested(NestedModel { v: 3 }),
+    }
+  );
+  let msg_: NewtypeMessage = model.pack_ref().unwrap();
+  assert_eq!(msg_, msg);
+
+  let err = NewtypeModel::unpack(NewtypeMessage {
+    nested: None,
+    ..msg
+  })
+  .err()
+  .unwrap();
+  assert_eq!(err.path(), vec![PathSegment::Field("nested")]);
+
+  let msg = DefaultMessage {
+    nested: Some(NestedMessage { v: 1 }),
+    nested_fn: Some(NestedMessage { v: 2 }),
+    count: Some(3),
+  };
+  let model = DefaultTuple::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    DefaultTuple(NestedModel { v: 1 }, NestedModel { v: 2 }, 3)
+  );
+  let msg_: DefaultMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let err = DefaultTuple::unpack_all_errors(DefaultMessage {
+    nested: None,
+    count: None,
+    ..msg
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    err.field_violations(),
+    vec![
+      FieldViolation {
+        field: "nested".to_string(),
+        description: "Could not unpack field 'nested' from null".to_string(),
+      },
+      FieldViolation {
+        field: "count".to_string(),
+        description: "Could not unpack field 'count' from null".to_string(),
+      },
+    ]
+  );
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(validate = "Email::validate")]
+struct Email(String);
+
+impl Email {
+  fn validate(&self) -> Result<(), &'static str> {
+    if self.0.contains('@') {
+      Ok(())
+    } else {
+      Err("missing '@'")
+    }
+  }
+}
+
+fn validate_percent(v: &f64) -> Result<(), String> {
+  if (0.0..=100.0).contains(v) {
+    Ok(())
+  } else {
+    Err(format!("{} is out of range", v))
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct ValidateMessage {
+  email: String,
+  percent: f64,
+  nested: Option<NestedMessage>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "NestedMessage", validate = "ValidateNested::validate")]
+struct ValidateNested {
+  v: i32,
+}
+
+impl ValidateNested {
+  fn validate(&self) -> Result<(), &'static str> {
+    if self.v > 0 {
+      Ok(())
+    } else {
+      Err("must be positive")
+    }
+  }
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "ValidateMessage")]
+struct ValidateModel {
+  email: Email,
+  #[protobuf_mapper(validate = "validate_percent")]
+  percent: f64,
+  nested: ValidateNested,
+}
+
+#[test]
+fn derive_validate() {
+  let msg = ValidateMessage {
+    email: "a@b.c".to_string(),
+    percent: 50.0,
+    nested: Some(NestedMessage { v: 1 }),
+  };
+  let model = ValidateModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    ValidateModel {
+      email: Email("a@b.c".to_string()),
+      percent: 50.0,
+      nested: ValidateNested { v: 1 },
+    }
+  );
+
+  let err = ValidateModel::unpack(ValidateMessage {
+    email: "abc".to_string(),
+    ..msg.clone()
+  })
+  .err()
+  .unwrap();
+  assert_eq!(err.path(), vec![PathSegment::Field("email")]);
+  assert_eq!(
+    format!("{}", err),
+    "Invalid value for field 'email': missing '@'"
+  );
+
+  let err = ValidateModel::unpack_all_errors(ValidateMessage {
+    email: "abc".to_string(),
+    percent: 101.0,
+    nested: Some(NestedMessage { v: 0 }),
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    err.field_violations(),
+    vec![
+      FieldViolation {
+        field: "email".to_string(),
+        description: "Invalid value for field 'email': missing '@'".to_string(),
+      },
+      FieldViolation {
+        field: "percent".to_string(),
+        description: "Invalid value for field 'percent': 101 is out of range".to_string(),
+      },
+      FieldViolation {
+        field: "nested".to_string(),
+        description: "Invalid value for field 'nested': must be positive".to_string(),
+      },
+    ]
+  );
+
+  let err = ValidateNested::unpack(NestedMessage { v: 0 })
+    .err()
+    .unwrap();
+  assert_eq!(format!("{}", err), "Invalid value: must be positive");
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct RulesMessage {
+  name: String,
+  code: String,
+  tags: Vec<String>,
+  counts: HashMap<String, i32>,
+  percent: f64,
+  quantity: i32,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "RulesMessage")]
+struct RulesModel {
+  #[protobuf_mapper(required, max_len = 5)]
+  name: String,
+  #[protobuf_mapper(pattern = "^[A-Z]{3}$")]
+  code: String,
+  #[protobuf_mapper(non_empty, max_len = 2)]
+  tags: Vec<String>,
+  #[protobuf_mapper(min_len = 1)]
+  counts: HashMap<String, i32>,
+  #[protobuf_mapper(range(min = 0.0, max = 100.0))]
+  percent: f64,
+  #[protobuf_mapper(range(min = 1))]
+  quantity: i32,
+}
+
+#[test]
+fn derive_validate_rules() {
+  let msg = RulesMessage {
+    name: "näme".to_string(),
+    code: "USD".to_string(),
+    tags: vec!["a".to_string()],
+    counts: vec![("a".to_string(), 1)].into_iter().collect(),
+    percent: 100.0,
+    quantity: 1,
+  };
+  let model = RulesModel::unpack(msg.clone()).unwrap();
+  let msg_: RulesMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let err = RulesModel::unpack_all_errors(RulesMessage::default())
+    .err()
+    .unwrap();
+  assert_eq!(
+    err.field_violations(),
+    vec![
+      FieldViolation {
+        field: "name".to_string(),
+        description: "Could not unpack field 'name' from null".to_string(),
+      },
+      FieldViolation {
+        field: "code".to_string(),
+        description: "Invalid value for field 'code': must match pattern '^[A-Z]{3}$'".to_string(),
+      },
+      FieldViolation {
+        field: "tags".to_string(),
+        description: "Invalid value for field 'tags': must not be empty".to_string(),
+      },
+      FieldViolation {
+        field: "counts".to_string(),
+        description: "Invalid value for field 'counts': length must be at least 1".to_string(),
+      },
+      FieldViolation {
+        field: "quantity".to_string(),
+        description: "Invalid value for field 'quantity': must be at least 1".to_string(),
+      },
+    ]
+  );
+
+  let err = RulesModel::unpack_all_errors(RulesMessage {
+    name: "toolong".to_string(),
+    tags: vec!["a".to_string(); 3],
+    percent: 100.5,
+    ..msg.clone()
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    err.field_violations(),
+    vec![
+      FieldViolation {
+        field: "name".to_string(),
+        description: "Invalid value for field 'name': length must be at most 5".to_string(),
+      },
+      FieldViolation {
+        field: "tags".to_string(),
+        description: "Invalid value for field 'tags': length must be at most 2".to_string(),
+      },
+      FieldViolation {
+        field: "percent".to_string(),
+        description: "Invalid value for field 'percent': must be at most 100".to_string(),
+      },
+    ]
+  );
+
+  let err = RulesModel::unpack(RulesMessage {
+    percent: f64::NAN,
+    ..msg
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "Invalid value for field 'percent': must be at least 0"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+enum ColorProto {
+  #[default]
+  Unspecified = 0,
+  Red = 1,
+  Blue = 2,
+}
+
+impl ColorProto {
+  fn from_i32(v: i32) -> Option<Self> {
+    match v {
+      0 => Some(ColorProto::Unspecified),
+      1 => Some(ColorProto::Red),
+      2 => Some(ColorProto::Blue),
+      _ => None,
+    }
+  }
+
+  fn as_str_name(&self) -> &'static str {
+    match self {
+      ColorProto::Unspecified => "COLOR_UNSPECIFIED",
+      ColorProto::Red => "COLOR_RED",
+      ColorProto::Blue => "COLOR_BLUE",
+    }
+  }
+
+  fn from_str_name(value: &str) -> Option<Self> {
+    match value {
+      "COLOR_UNSPECIFIED" => Some(ColorProto::Unspecified),
+      "COLOR_RED" => Some(ColorProto::Red),
+      "COLOR_BLUE" => Some(ColorProto::Blue),
+      _ => None,
+    }
+  }
+}
+
+impl From<ColorProto> for i32 {
+  fn from(v: ColorProto) -> Self {
+    v as i32
+  }
+}
+
+#[derive(Debug, ProtoEnum, PartialEq, Clone, Copy)]
+#[protobuf_mapper(proto_enum_type = "ColorProto", unspecified = "Unspecified")]
+enum ColorModel {
+  Red,
+  Blue,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct ColorMessage {
+  color: i32,
+  optional_color: i32,
+  tagged_color: i32,
+}
+
+impl ColorMessage {
+  fn set_tagged_color(&mut self, v: ColorProto) {
+    self.tagged_color = v.into();
+  }
+
+  fn tagged_color(&self) -> ColorProto {
+    ColorProto::from_i32(self.tagged_color).unwrap_or_default()
+  }
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "ColorMessage", pack_ref)]
+struct ColorMessageModel {
+  color: ColorModel,
+  optional_color: Option<ColorModel>,
+  #[protobuf_mapper(proto_enum)]
+  tagged_color: ColorModel,
+}
+
+#[test]
+fn derive_enum_unspecified() {
+  assert_eq!(ColorModel::from_i32(0), None);
+  assert_eq!(ColorModel::from_i32(2), Some(ColorModel::Blue));
+  assert_eq!(
+    format!("{}", ColorModel::unpack_i32(0).err().unwrap()),
+    "Enum value is unspecified: enum type = ColorModel"
+  );
+  assert_eq!(
+    ColorModel::unpack_enum(ColorProto::Red).unwrap(),
+    ColorModel::Red
+  );
+  assert_eq!(
+    format!(
+      "{}",
+      ColorModel::unpack_enum(ColorProto::Unspecified)
+        .err()
+        .unwrap()
+    ),
+    "Enum value is unspecified: enum type = ColorModel"
+  );
+
+  let msg = ColorMessage {
+    color: 1,
+    optional_color: 0,
+    tagged_color: 2,
+  };
+  let model = ColorMessageModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    ColorMessageModel {
+      color: ColorModel::Red,
+      optional_color: None,
+      tagged_color: ColorModel::Blue,
+    }
+  );
+  let msg_: ColorMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let msg: ColorMessage = ColorMessageModel {
+    color: ColorModel::Blue,
+    optional_color: Some(ColorModel::Red),
+    tagged_color: ColorModel::Red,
+  }
+  .pack_ref()
+  .unwrap();
+  assert_eq!(
+    msg,
+    ColorMessage {
+      color: 2,
+      optional_color: 1,
+      tagged_color: 1,
+    }
+  );
+
+  let err = ColorMessageModel::unpack_all_errors(ColorMessage::default())
+    .err()
+    .unwrap();
+  assert_eq!(
+    err.field_violations(),
+    vec![
+      FieldViolation {
+        field: "color".to_string(),
+        description: "Enum value is unspecified: enum type = ColorModel".to_string(),
+      },
+      FieldViolation {
+        field: "tagged_color".to_string(),
+        description: "Enum value is unspecified: enum type = ColorModel".to_string(),
+      },
+    ]
+  );
+}
+
+#[derive(Debug, ProtoEnum, PartialEq, Clone, Copy)]
+#[protobuf_mapper(
+  proto_enum_type = "ColorProto",
+  unspecified = "Unspecified",
+  from_str,
+  display
+)]
+enum OpenColorModel {
+  Red,
+  Blue,
+  #[protobuf_mapper(other)]
+  Unknown(i32),
+}
+
+#[test]
+fn derive_enum_other() {
+  assert_eq!(OpenColorModel::from_i32(1), Some(OpenColorModel::Red));
+  assert_eq!(OpenColorModel::from_i32(0), None);
+  assert_eq!(
+    OpenColorModel::from_i32(7),
+    Some(OpenColorModel::Unknown(7))
+  );
+  assert_eq!(OpenColorModel::Unknown(7).get_variant_name(), "Unknown");
+
+  let model = OpenColorModel::unpack(7).unwrap();
+  assert_eq!(model, OpenColorModel::Unknown(7));
+  assert_eq!(ProtoPackRef::<i32>::pack_ref(&model).unwrap(), 7);
+  assert_eq!(ProtoPack::<i32>::pack(model).unwrap(), 7);
+  assert_eq!(model.to_proto_enum(), ColorProto::default());
+  assert_eq!(
+    format!("{}", model.try_to_proto_enum().err().unwrap()),
+    "Enum discriminant is not found: enum type = ColorProto, discriminant = 7"
+  );
+
+  assert_eq!(ProtoPack::<i32>::pack(OpenColorModel::Blue).unwrap(), 2);
+  assert_eq!(
+    format!("{}", OpenColorModel::unpack(0).err().unwrap()),
+    "Enum value is unspecified: enum type = OpenColorModel"
+  );
+
+  let model = OpenColorMessageModel {
+    colors: vec![OpenColorModel::Unknown(7)],
+    tagged_color: OpenColorModel::Blue,
+  };
+  let msg: OpenColorMessage = model.pack_ref().unwrap();
+  assert_eq!(msg.colors, vec![7]);
+  assert_eq!(OpenColorMessageModel::unpack(msg).unwrap(), model);
+
+  let err = ProtoPack::<OpenColorMessage>::pack(OpenColorMessageModel {
+    colors: vec![],
+    tagged_color: OpenColorModel::Unknown(7),
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "tagged_color: Enum discriminant is not found: enum type = ColorProto, discriminant = 7"
+  );
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct OpenColorMessage {
+  colors: Vec<i32>,
+  tagged_color: i32,
+}
+
+impl OpenColorMessage {
+  fn set_tagged_color(&mut self, v: ColorProto) {
+    self.tagged_color = v.into();
+  }
+
+  fn tagged_color(&self) -> ColorProto {
+    ColorProto::from_i32(self.tagged_color).unwrap_or_default()
+  }
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "OpenColorMessage", pack_ref)]
+struct OpenColorMessageModel {
+  #[protobuf_mapper(proto_enum, strict)]
+  colors: Vec<OpenColorModel>,
+  #[protobuf_mapper(proto_enum)]
+  tagged_color: OpenColorModel,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ShadeProto {
+  Unspecified = 0,
+  Red = 1,
+  Blue = 2,
+}
+
+impl ShadeProto {
+  fn from_i32(v: i32) -> Option<Self> {
+    match v {
+      0 => Some(ShadeProto::Unspecified),
+      1 => Some(ShadeProto::Red),
+      2 => Some(ShadeProto::Blue),
+      _ => None,
+    }
+  }
+
+  fn as_str_name(&self) -> &'static str {
+    match self {
+      ShadeProto::Unspecified => "SHADE_UNSPECIFIED",
+      ShadeProto::Red => "SHADE_RED",
+      ShadeProto::Blue => "SHADE_BLUE",
+    }
+  }
+
+  fn from_str_name(value: &str) -> Option<Self> {
+    match value {
+      "SHADE_UNSPECIFIED" => Some(ShadeProto::Unspecified),
+      "SHADE_RED" => Some(ShadeProto::Red),
+      "SHADE_BLUE" => Some(ShadeProto::Blue),
+      _ => None,
+    }
+  }
+}
+
+impl From<ShadeProto> for i32 {
+  fn from(v: ShadeProto) -> Self {
+    v as i32
+  }
+}
+
+#[derive(Debug, ProtoEnum, PartialEq, Clone, Copy)]
+#[protobuf_mapper(
+  proto_enum_type(ColorProto, ShadeProto),
+  unspecified = "Unspecified",
+  proto_names
+)]
+enum PaletteModel {
+  Red,
+  Blue,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct EnumContainerMessage {
+  colors: Vec<i32>,
+  by_name: HashMap<String, i32>,
+  ordered: IndexMap<String, i32>,
+  maybe: Option<i32>,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "EnumContainerMessage", pack_ref, strict_enums)]
+struct EnumContainerModel {
+  #[protobuf_mapper(proto_enum)]
+  colors: Vec<ColorModel>,
+  #[protobuf_mapper(proto_enum, proto_enum_type = "ShadeProto")]
+  by_name: HashMap<String, PaletteModel>,
+  #[protobuf_mapper(proto_enum)]
+  ordered: IndexMap<String, ColorModel>,
+  #[protobuf_mapper(proto_enum, proto_enum_type = "ColorProto")]
+  maybe: Option<PaletteModel>,
+}
+
+#[test]
+fn derive_enum_containers() {
+  let msg = EnumContainerMessage {
+    colors: vec![1, 2],
+    by_name: vec![("sky".to_string(), 2)].into_iter().collect(),
+    ordered: vec![("rose".to_string(), 1)].into_iter().collect(),
+    maybe: Some(1),
+  };
+  let model = EnumContainerModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    EnumContainerModel {
+      colors: vec![ColorModel::Red, ColorModel::Blue],
+      by_name: vec![("sky".to_string(), PaletteModel::Blue)]
+        .into_iter()
+        .collect(),
+      ordered: vec![("rose".to_string(), ColorModel::Red)]
+        .into_iter()
+        .collect(),
+      maybe: Some(PaletteModel::Red),
+    }
+  );
+  let msg_: EnumContainerMessage = model.pack_ref().unwrap();
+  assert_eq!(msg_, msg);
+  let msg_: EnumContainerMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let err = EnumContainerModel::unpack_all_errors(EnumContainerMessage {
+    colors: vec![1, 9, 0],
+    by_name: vec![("sky".to_string(), 0)].into_iter().collect(),
+    ordered: vec![("rose".to_string(), 5)].into_iter().collect(),
+    maybe: None,
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    err.field_violations(),
+    vec![
+      FieldViolation {
+        field: "colors[1]".to_string(),
+        description: "Enum discriminant is not found: enum type = ColorModel, discriminant = 9"
+          .to_string(),
+      },
+      FieldViolation {
+        field: "colors[2]".to_string(),
+        description: "Enum value is unspecified: enum type = ColorModel".to_string(),
+      },
+      FieldViolation {
+        field: "by_name[\"sky\"]".to_string(),
+        description: "Enum value is unspecified: enum type = PaletteModel".to_string(),
+      },
+      FieldViolation {
+        field: "ordered[\"rose\"]".to_string(),
+        description: "Enum discriminant is not found: enum type = ColorModel, discriminant = 5"
+          .to_string(),
+      },
+    ]
+  );
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "ColorMessage", pack_defaults)]
+struct StrictColorModel {
+  #[protobuf_mapper(proto_enum, strict)]
+  tagged_color: ColorModel,
+}
+
+#[test]
+fn derive_enum_strict() {
+  let msg = ColorMessage {
+    tagged_color: 2,
+    ..Default::default()
+  };
+  let model = StrictColorModel::unpack(msg.clone()).unwrap();
+  assert_eq!(model.tagged_color, ColorModel::Blue);
+  let msg_: ColorMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  // prost's getter would read 9 as the default value.
+  let msg = ColorMessage {
+    tagged_color: 9,
+    ..Default::default()
+  };
+  assert_eq!(msg.tagged_color(), ColorProto::Unspecified);
+  let err = StrictColorModel::unpack(msg).err().unwrap();
+  assert_eq!(err.path(), vec![PathSegment::Field("tagged_color")]);
+  assert_eq!(
+    format!("{}", err),
+    "tagged_color: Enum discriminant is not found: enum type = ColorModel, discriminant = 9"
+  );
+
+  let err = LenientColorModel::unpack(ColorMessage {
+    tagged_color: 9,
+    ..Default::default()
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    format!("{}", err),
+    "tagged_color: Enum value is unspecified: enum type = ColorModel"
+  );
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "ColorMessage", pack_defaults, strict_enums)]
+struct LenientColorModel {
+  #[protobuf_mapper(proto_enum, strict = false)]
+  tagged_color: ColorModel,
+}
+
+#[derive(Debug, ProtoEnum, PartialEq, Clone, Copy)]
+#[protobuf_mapper(
+  proto_enum_type = "ColorProto",
+  unspecified = "Unspecified",
+  from_str,
+  display
+)]
+enum NamedColorModel {
+  #[protobuf_mapper(rename = "Red")]
+  Crimson,
+  Blue,
+}
+
+#[test]
+fn derive_enum_names() {
+  assert_eq!(
+    NamedColorModel::VARIANTS,
+    &[NamedColorModel::Crimson, NamedColorModel::Blue]
+  );
+  assert_eq!(
+    NamedColorModel::from_variant_name("Crimson"),
+    Some(NamedColorModel::Crimson)
+  );
+  assert_eq!(NamedColorModel::from_variant_name("Red"), None);
+  assert_eq!(NamedColorModel::Crimson.proto_name(), Some("COLOR_RED"));
+  assert_eq!(
+    NamedColorModel::from_proto_name("COLOR_RED"),
+    Some(NamedColorModel::Crimson)
+  );
+  assert_eq!(NamedColorModel::from_proto_name("COLOR_UNSPECIFIED"), None);
+
+  assert_eq!(NamedColorModel::Crimson.to_string(), "COLOR_RED");
+  assert_eq!(
+    "COLOR_BLUE".parse::<NamedColorModel>().unwrap(),
+    NamedColorModel::Blue
+  );
+  assert_eq!(
+    "Blue".parse::<NamedColorModel>().err().unwrap().to_string(),
+    "Enum name is not found: enum type = NamedColorModel, name = Blue"
+  );
+
+  assert_eq!(OpenColorModel::VARIANTS.len(), 2);
+  assert_eq!(OpenColorModel::Unknown(7).proto_name(), None);
+  assert_eq!(OpenColorModel::from_variant_name("Unknown"), None);
+  assert_eq!(OpenColorModel::Unknown(7).to_string(), "7");
+  assert_eq!(
+    "7".parse::<OpenColorModel>().unwrap(),
+    OpenColorModel::Unknown(7)
+  );
+  assert_eq!(
+    "COLOR_RED".parse::<OpenColorModel>().unwrap(),
+    OpenColorModel::Red
+  );
+
+  assert_eq!(
+    ProtoEnumName::<ShadeProto>::proto_name(&PaletteModel::Red),
+    Some("SHADE_RED")
+  );
+  assert_eq!(
+    <PaletteModel as ProtoEnumName<ColorProto>>::from_proto_name("COLOR_BLUE"),
+    Some(PaletteModel::Blue)
+  );
+}
+
+// Variants named exactly like the `.proto` file.
+#[allow(non_camel_case_types)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum OrderStatus {
+  ORDER_STATUS_UNSPECIFIED = 0,
+  ORDER_STATUS_PLACED = 1,
+  ORDER_STATUS_SHIPPED = 2,
+}
+
+impl OrderStatus {
+  fn from_i32(v: i32) -> Option<Self> {
+    match v {
+      0 => Some(OrderStatus::ORDER_STATUS_UNSPECIFIED),
+      1 => Some(OrderStatus::ORDER_STATUS_PLACED),
+      2 => Some(OrderStatus::ORDER_STATUS_SHIPPED),
+      _ => None,
+    }
+  }
+
+  fn as_str_name(&self) -> &'static str {
+    match self {
+      OrderStatus::ORDER_STATUS_UNSPECIFIED => "ORDER_STATUS_UNSPECIFIED",
+      OrderStatus::ORDER_STATUS_PLACED => "ORDER_STATUS_PLACED",
+      OrderStatus::ORDER_STATUS_SHIPPED => "ORDER_STATUS_SHIPPED",
+    }
+  }
+
+  fn from_str_name(value: &str) -> Option<Self> {
+    match value {
+      "ORDER_STATUS_UNSPECIFIED" => Some(OrderStatus::ORDER_STATUS_UNSPECIFIED),
+      "ORDER_STATUS_PLACED" => Some(OrderStatus::ORDER_STATUS_PLACED),
+      "ORDER_STATUS_SHIPPED" => Some(OrderStatus::ORDER_STATUS_SHIPPED),
+      _ => None,
+    }
+  }
+}
+
+impl From<OrderStatus> for i32 {
+  fn from(v: OrderStatus) -> Self {
+    v as i32
+  }
+}
+
+#[derive(Debug, ProtoEnum, PartialEq, Clone, Copy)]
+#[protobuf_mapper(
+  proto_enum_type = "OrderStatus",
+  unspecified = "ORDER_STATUS_UNSPECIFIED",
+  rename_all = "SCREAMING_SNAKE_CASE",
+  prefix = "ORDER_STATUS_",
+  proto_names
+)]
+enum OrderStatusModel {
+  Placed,
+  #[protobuf_mapper(rename = "ORDER_STATUS_SHIPPED")]
+  Sent,
+}
+
+#[test]
+fn derive_enum_rename_all() {
+  assert_eq!(
+    OrderStatusModel::Placed.into_proto_enum(),
+    OrderStatus::ORDER_STATUS_PLACED
+  );
+  assert_eq!(
+    OrderStatusModel::unpack_i32(2).unwrap(),
+    OrderStatusModel::Sent
+  );
+  assert!(matches!(
+    OrderStatusModel::unpack_i32(0),
+    Err(protobuf_mapper::result::Error::EnumUnspecified { .. })
+  ));
+  assert_eq!(
+    OrderStatusModel::Placed.proto_name(),
+    Some("ORDER_STATUS_PLACED")
+  );
+
+  assert_eq!(
+    LegacyStatusModel::Shipped.into_proto_enum(),
+    LegacyStatus::StatusShipped
+  );
+  assert_eq!(
+    LegacyStatusModel::unpack_i32(1).unwrap(),
+    LegacyStatusModel::Placed
+  );
+}
+
+// prost strips only the enum's own prefix, `LEGACY_STATUS_`, so it keeps `STATUS_` of
+// `STATUS_PLACED` in PascalCase.
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum LegacyStatus {
+  StatusUnspecified = 0,
+  StatusPlaced = 1,
+  StatusShipped = 2,
+}
+
+impl LegacyStatus {
+  fn from_i32(v: i32) -> Option<Self> {
+    match v {
+      0 => Some(LegacyStatus::StatusUnspecified),
+      1 => Some(LegacyStatus::StatusPlaced),
+      2 => Some(LegacyStatus::StatusShipped),
+      _ => None,
+    }
+  }
+}
+
+impl From<LegacyStatus> for i32 {
+  fn from(v: LegacyStatus) -> Self {
+    v as i32
+  }
+}
+
+#[derive(Debug, ProtoEnum, PartialEq, Clone, Copy)]
+#[protobuf_mapper(
+  proto_enum_type = "LegacyStatus",
+  unspecified = "StatusUnspecified",
+  prefix = "Status"
+)]
+enum LegacyStatusModel {
+  Placed,
+  Shipped,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, PartialEq, Clone, Default)]
+struct CamelMessage {
+  userId: i32,
+  displayName: String,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "CamelMessage", rename_all = "camelCase")]
+struct CamelModel {
+  user_id: i32,
+  display_name: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct RawMessage {
+  raw_id_value: i32,
+  raw_name_value: String,
+  label: String,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(
+  message_type = "RawMessage",
+  field_prefix = "raw_",
+  field_suffix = "_value"
+)]
+struct RawModel {
+  id: i32,
+  name: String,
+  #[protobuf_mapper(rename = "label")]
+  title: String,
+}
+
+#[test]
+fn derive_rename_all() {
+  let msg = CamelMessage {
+    userId: 1,
+    displayName: "Ada".to_string(),
+  };
+  let model = CamelModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    CamelModel {
+      user_id: 1,
+      display_name: "Ada".to_string(),
+    }
+  );
+  let msg_: CamelMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let msg = RawMessage {
+    raw_id_value: 2,
+    raw_name_value: "Grace".to_string(),
+    label: "Rear Admiral".to_string(),
+  };
+  let model = RawModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    RawModel {
+      id: 2,
+      name: "Grace".to_string(),
+      title: "Rear Admiral".to_string(),
+    }
+  );
+  let msg_: RawMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let msg = KeywordMessage {
+    r#type: "admin".to_string(),
+    userId: 3,
+  };
+  let model = KeywordModel::unpack(msg.clone()).unwrap();
+  assert_eq!(model.r#type, "admin");
+  assert_eq!(model.user_id, 3);
+  let msg_: KeywordMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+  let model = PlainKeywordModel::unpack(msg.clone()).unwrap();
+  let msg_: KeywordMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, PartialEq, Clone, Default)]
+struct KeywordMessage {
+  r#type: String,
+  userId: i32,
+}
+
+// `type` is a keyword again after `rename_all`, so it maps to `r#type`.
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "KeywordMessage", rename_all = "camelCase")]
+struct KeywordModel {
+  r#type: String,
+  user_id: i32,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "KeywordMessage")]
+struct PlainKeywordModel {
+  r#type: String,
+  #[protobuf_mapper(rename = "userId")]
+  user_id: i32,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, PartialEq, Clone, Default)]
+struct CamelColorMessage {
+  taggedColor: i32,
+  r#type: i32,
+}
+
+#[allow(non_snake_case)]
+impl CamelColorMessage {
+  fn set_taggedColor(&mut self, v: ColorProto) {
+    self.taggedColor = v.into();
+  }
+
+  fn taggedColor(&self) -> ColorProto {
+    ColorProto::from_i32(self.taggedColor).unwrap_or_default()
+  }
+
+  fn set_type(&mut self, v: ColorProto) {
+    self.r#type = v.into();
+  }
+
+  fn r#type(&self) -> ColorProto {
+    ColorProto::from_i32(self.r#type).unwrap_or_default()
+  }
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "CamelColorMessage", rename_all = "camelCase", pack_ref)]
+struct CamelColorModel {
+  #[protobuf_mapper(proto_enum)]
+  tagged_color: ColorModel,
+  #[protobuf_mapper(proto_enum)]
+  r#type: ColorModel,
+}
+
+#[test]
+fn derive_rename_all_proto_enum() {
+  let msg = CamelColorMessage {
+    taggedColor: 1,
+    r#type: 2,
+  };
+  let model = CamelColorModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    CamelColorModel {
+      tagged_color: ColorModel::Red,
+      r#type: ColorModel::Blue,
+    }
+  );
+  let msg_: CamelColorMessage = model.pack_ref().unwrap();
+  assert_eq!(msg_, msg);
+  let msg_: CamelColorMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  let err = CamelColorModel::unpack_all_errors(CamelColorMessage::default())
+    .err()
+    .unwrap();
+  assert_eq!(
+    err.field_violations(),
+    vec![
+      FieldViolation {
+        field: "tagged_color".to_string(),
+        description: "Enum value is unspecified: enum type = ColorModel".to_string(),
+      },
+      FieldViolation {
+        field: "type".to_string(),
+        description: "Enum value is unspecified: enum type = ColorModel".to_string(),
+      },
+    ]
+  );
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(
+  message_type = "RawMessage",
+  field_prefix = "raw_",
+  field_suffix = "_value",
+  exhaustive,
+  ignore_proto_fields(label)
+)]
+struct ExhaustiveRawModel {
+  id: i32,
+  #[protobuf_mapper(skip_unpack)]
+  name: String,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(
+  message_type = "RawMessage",
+  field_prefix = "raw_",
+  field_suffix = "_value",
+  exhaustive,
+  ignore_proto_fields(label)
+)]
+struct ExhaustiveSkipPackModel {
+  id: i32,
+  #[protobuf_mapper(skip_pack)]
+  name: String,
+}
+
+// Naming a mapped field in `ignore_proto_fields` is harmless.
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(
+  message_type = "RawMessage",
+  field_prefix = "raw_",
+  field_suffix = "_value",
+  exhaustive,
+  ignore_proto_fields(label, raw_name_value)
+)]
+struct ExhaustiveIgnoredSkipPackModel {
+  id: i32,
+  #[protobuf_mapper(skip_pack)]
+  name: String,
+}
+
+#[test]
+fn derive_exhaustive() {
+  let msg = RawMessage {
+    raw_id_value: 3,
+    raw_name_value: "Linus".to_string(),
+    label: "ignored".to_string(),
+  };
+  let model = ExhaustiveRawModel::unpack(msg).unwrap();
+  assert_eq!(
+    model,
+    ExhaustiveRawModel {
+      id: 3,
+      name: String::new(),
+    }
+  );
+  let msg: RawMessage = ExhaustiveRawModel {
+    id: 4,
+    name: "Ken".to_string(),
+  }
+  .pack()
+  .unwrap();
+  assert_eq!(
+    msg,
+    RawMessage {
+      raw_id_value: 4,
+      raw_name_value: "Ken".to_string(),
+      label: String::new(),
+    }
+  );
+
+  let msg = RawMessage {
+    raw_id_value: 5,
+    raw_name_value: "Barbara".to_string(),
+    label: "ignored".to_string(),
+  };
+  let model = ExhaustiveSkipPackModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    ExhaustiveSkipPackModel {
+      id: 5,
+      name: "Barbara".to_string(),
+    }
+  );
+  let msg_: RawMessage = model.pack().unwrap();
+  assert_eq!(
+    msg_,
+    RawMessage {
+      raw_id_value: 5,
+      raw_name_value: String::new(),
+      label: String::new(),
+    }
+  );
+  let model = ExhaustiveIgnoredSkipPackModel::unpack(msg).unwrap();
+  assert_eq!(model.name, "Barbara");
+  let msg_: RawMessage = model.pack().unwrap();
+  assert_eq!(msg_.raw_name_value, "");
+}
+
+#[test]
+fn derive_compile_errors() {
+  trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct AddressMessage {
+  city: String,
+  street: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct CustomerMessage {
+  name: String,
+  address: Option<AddressMessage>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct FlatCustomerMessage {
+  name: String,
+  city: String,
+  street: String,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "CustomerMessage", pack_ref)]
+struct CustomerModel {
+  name: String,
+  #[protobuf_mapper(from = "address.city", non_empty)]
+  city: String,
+  #[protobuf_mapper(from = "address.street", default)]
+  street: String,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(
+  message_type(AddressMessage, FlatCustomerMessage),
+  pack_defaults,
+  pack_ref
+)]
+struct AddressModel {
+  #[protobuf_mapper(non_empty)]
+  city: String,
+  street: String,
+}
+
+#[derive(Debug, ProtoPack, ProtoUnpack, PartialEq)]
+#[protobuf_mapper(message_type = "FlatCustomerMessage", pack_ref)]
+struct FlatCustomerModel {
+  name: String,
+  #[protobuf_mapper(flatten)]
+  address: AddressModel,
+}
+
+#[test]
+fn derive_from_path() {
+  let msg = CustomerMessage {
+    name: "Ada".to_string(),
+    address: Some(AddressMessage {
+      city: "London".to_string(),
+      street: "St James's Square".to_string(),
+    }),
+  };
+  let model = CustomerModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    CustomerModel {
+      name: "Ada".to_string(),
+      city: "London".to_string(),
+      street: "St James's Square".to_string(),
+    }
+  );
+  let msg_: CustomerMessage = model.pack_ref().unwrap();
+  assert_eq!(msg_, msg);
+  let msg_: CustomerMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  // An absent nested message is a missing value, unless the field has a `default`.
+  let msg = CustomerMessage {
+    name: "Ada".to_string(),
+    address: None,
+  };
+  let err = CustomerModel::unpack(msg.clone()).err().unwrap();
+  assert!(matches!(
+    err,
+    protobuf_mapper::result::Error::FieldValueNotPresent { .. }
+  ));
+  assert_eq!(err.path(), vec![PathSegment::Field("city")]);
+  let errors = CustomerModel::unpack_all_errors(msg).err().unwrap();
+  assert_eq!(
+    errors.field_violations(),
+    vec![FieldViolation {
+      field: "city".to_string(),
+      description: "Could not unpack field 'city' from null".to_string(),
+    }]
+  );
+}
+
+#[test]
+fn derive_flatten() {
+  let msg = FlatCustomerMessage {
+    name: "Ada".to_string(),
+    city: "London".to_string(),
+    street: "St James's Square".to_string(),
+  };
+  let model = FlatCustomerModel::unpack(msg.clone()).unwrap();
+  assert_eq!(
+    model,
+    FlatCustomerModel {
+      name: "Ada".to_string(),
+      address: AddressModel {
+        city: "London".to_string(),
+        street: "St James's Square".to_string(),
+      },
+    }
+  );
+  let msg_: FlatCustomerMessage = model.pack_ref().unwrap();
+  assert_eq!(msg_, msg);
+  let msg_: FlatCustomerMessage = model.pack().unwrap();
+  assert_eq!(msg_, msg);
+
+  // Errors of the flattened struct are under the Rust field.
+  let errors = FlatCustomerModel::unpack_all_errors(FlatCustomerMessage {
+    name: "Ada".to_string(),
+    ..Default::default()
+  })
+  .err()
+  .unwrap();
+  assert_eq!(
+    errors.field_violations(),
+    vec![FieldViolation {
+      field: "address.city".to_string(),
+      description: "Invalid value for field 'city': must not be empty".to_string(),
+    }]
+  );
+}
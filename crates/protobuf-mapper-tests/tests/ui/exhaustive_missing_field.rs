@@ -0,0 +1,16 @@
+use protobuf_mapper::{ProtoPack, ProtoUnpack};
+
+#[derive(Debug, Default)]
+struct UserMessage {
+  id: i32,
+  name: String,
+  email: String,
+}
+
+#[derive(ProtoPack, ProtoUnpack)]
+#[protobuf_mapper(message_type = "UserMessage", exhaustive, ignore_proto_fields(email))]
+struct UserModel {
+  id: i32,
+}
+
+fn main() {}
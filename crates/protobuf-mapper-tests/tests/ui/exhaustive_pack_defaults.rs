@@ -0,0 +1,15 @@
+use protobuf_mapper::{ProtoPack, ProtoUnpack};
+
+#[derive(Debug, Default)]
+struct UserMessage {
+  id: i32,
+  name: String,
+}
+
+#[derive(ProtoPack, ProtoUnpack)]
+#[protobuf_mapper(message_type = "UserMessage", exhaustive, pack_defaults)]
+struct UserModel {
+  id: i32,
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use protobuf_mapper::{ProtoPack, ProtoUnpack};
+
+#[derive(Debug, Default)]
+struct UserMessage {
+  id: i32,
+}
+
+fn double(v: i32) -> i32 {
+  v * 2
+}
+
+#[derive(ProtoPack, ProtoUnpack)]
+#[protobuf_mapper(message_type = "UserMessage")]
+struct UserModel {
+  #[protobuf_mapper(map_fn = "double", default)]
+  id: i32,
+}
+
+fn main() {}
@@ -1,15 +1,21 @@
 use bigdecimal::BigDecimal;
-use chrono::{DateTime, Utc};
-use prost_types;
+use chrono::{DateTime, TimeZone, Utc};
 use prost_types::{Timestamp, Value};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::cmp::Eq;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Debug;
 use std::hash::Hash;
+use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::result::{self, Result};
-use crate::{ProtoPack, ProtoUnpack};
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
+
+use crate::result::{self, Errors, MapEntryPart, Result};
+use crate::wrappers;
+use crate::{ProtoPack, ProtoPackRef, ProtoUnpack};
 
 macro_rules! impl_option {
   ($rust:ty => $proto:ty) => {
@@ -19,6 +25,12 @@ macro_rules! impl_option {
       }
     }
 
+    impl ProtoPackRef<Option<$proto>> for $rust {
+      fn pack_ref(&self) -> Result<Option<$proto>> {
+        Ok(Some(self.pack_ref()?))
+      }
+    }
+
     impl ProtoUnpack<Option<$proto>> for $rust {
       fn unpack(value: Option<$proto>) -> Result<$rust> {
         if let Some(value) = value {
@@ -39,6 +51,12 @@ impl ProtoPack<Value> for JsonValue {
   }
 }
 
+impl ProtoPackRef<Value> for JsonValue {
+  fn pack_ref(&self) -> Result<Value> {
+    json_value_ref_to_value(self)
+  }
+}
+
 impl ProtoUnpack<Value> for JsonValue {
   fn unpack(value: Value) -> Result<JsonValue> {
     value_to_json_value(value)
@@ -97,7 +115,6 @@ fn value_to_json_value(value: Value) -> Result<JsonValue> {
 fn json_value_to_value(value: JsonValue) -> Result<Value> {
   fn convert(nest: usize, value: JsonValue) -> Result<Value> {
     use prost_types::{value::Kind, ListValue, Struct};
-    use std::collections::BTreeMap;
 
     if nest >= MAX_JSON_NEST {
       return Err(result::Error::JsonValueNestedTooDeeply);
@@ -137,6 +154,49 @@ fn json_value_to_value(value: JsonValue) -> Result<Value> {
   convert(0, value)
 }
 
+/// Same as `json_value_to_value`, copying the strings out of the borrowed value.
+fn json_value_ref_to_value(value: &JsonValue) -> Result<Value> {
+  fn convert(nest: usize, value: &JsonValue) -> Result<Value> {
+    use prost_types::{value::Kind, ListValue, Struct};
+
+    if nest >= MAX_JSON_NEST {
+      return Err(result::Error::JsonValueNestedTooDeeply);
+    }
+
+    let kind = match value {
+      JsonValue::Null => Kind::NullValue(0),
+      JsonValue::Bool(v) => Kind::BoolValue(*v),
+      JsonValue::Number(v) => {
+        if let Some(v) = v.as_f64() {
+          Kind::NumberValue(v)
+        } else {
+          Kind::NullValue(0)
+        }
+      }
+      JsonValue::String(v) => Kind::StringValue(v.clone()),
+      JsonValue::Array(values) => {
+        let mut value_values = Vec::with_capacity(values.len());
+        for v in values {
+          value_values.push(convert(nest + 1, v)?);
+        }
+        Kind::ListValue(ListValue {
+          values: value_values,
+        })
+      }
+      JsonValue::Object(map) => {
+        let mut value_map = BTreeMap::new();
+        for (k, v) in map {
+          value_map.insert(k.clone(), convert(nest + 1, v)?);
+        }
+        Kind::StructValue(Struct { fields: value_map })
+      }
+    };
+    Ok(Value { kind: Some(kind) })
+  }
+
+  convert(0, value)
+}
+
 /// Helper type to convert any serializable type from/to `google.protobuf.Value`
 pub struct Json<T>(pub T);
 
@@ -149,6 +209,15 @@ where
   }
 }
 
+impl<T> ProtoPackRef<Value> for Json<T>
+where
+  T: Serialize + for<'de> Deserialize<'de>,
+{
+  fn pack_ref(&self) -> Result<Value> {
+    pack_value(&self.0)
+  }
+}
+
 impl<T> ProtoUnpack<Value> for Json<T>
 where
   T: Serialize + for<'de> Deserialize<'de>,
@@ -184,10 +253,22 @@ impl ProtoPack<Timestamp> for DateTime<Utc> {
   }
 }
 
+impl ProtoPackRef<Timestamp> for DateTime<Utc> {
+  fn pack_ref(&self) -> Result<Timestamp> {
+    (*self).pack()
+  }
+}
+
 impl ProtoUnpack<Timestamp> for DateTime<Utc> {
   fn unpack(Timestamp { seconds, nanos }: Timestamp) -> Result<DateTime<Utc>> {
-    let dt = chrono::NaiveDateTime::from_timestamp(seconds, nanos as u32);
-    Ok(DateTime::from_utc(dt, Utc))
+    let out_of_range = || result::Error::TimestampOutOfRange { seconds, nanos };
+    if !(0..1_000_000_000).contains(&nanos) {
+      return Err(out_of_range());
+    }
+    Utc
+      .timestamp_opt(seconds, nanos as u32)
+      .single()
+      .ok_or_else(out_of_range)
   }
 }
 
@@ -208,13 +289,19 @@ impl ProtoPack<prost_types::Duration> for chrono::Duration {
   }
 }
 
+impl ProtoPackRef<prost_types::Duration> for chrono::Duration {
+  fn pack_ref(&self) -> Result<prost_types::Duration> {
+    (*self).pack()
+  }
+}
+
 impl ProtoUnpack<prost_types::Duration> for chrono::Duration {
   fn unpack(value: prost_types::Duration) -> Result<chrono::Duration> {
     let std_duration = <prost_types::Duration as TryInto<std::time::Duration>>::try_into(value)
       .map_err(|e| result::Error::ParseDuration {
         message: format!(
           "Source duration value is out of range for the target type: {}",
-          e.to_string()
+          e
         ),
       })?;
 
@@ -235,6 +322,12 @@ impl ProtoPack<String> for BigDecimal {
   }
 }
 
+impl ProtoPackRef<String> for BigDecimal {
+  fn pack_ref(&self) -> Result<String> {
+    Ok(self.to_string())
+  }
+}
+
 impl<T> ProtoUnpack<T> for BigDecimal
 where
   T: AsRef<str>,
@@ -257,6 +350,12 @@ macro_rules! impl_self {
         }
       }
 
+      impl ProtoPackRef<$ty> for $ty {
+        fn pack_ref(&self) -> Result<$ty> {
+          Ok(self.clone())
+        }
+      }
+
       impl ProtoUnpack<$ty> for $ty {
         fn unpack(value: $ty) -> Result<$ty> {
           Ok(value)
@@ -278,6 +377,62 @@ impl_self! {
   Vec<u8>
 }
 
+// prost generates both proto3 `optional` scalars and wrapper types as `Option<T>`
+
+impl_option!(f32 => f32);
+impl_option!(f64 => f64);
+impl_option!(i64 => i64);
+impl_option!(u64 => u64);
+impl_option!(i32 => i32);
+impl_option!(u32 => u32);
+impl_option!(bool => bool);
+impl_option!(String => String);
+impl_option!(Vec<u8> => Vec<u8>);
+
+// Wrapper messages
+
+macro_rules! impl_wrapper {
+  (
+    $($ty:ty => $wrapper:ident),*
+  ) => {
+    $(
+      impl ProtoPack<wrappers::$wrapper> for $ty {
+        fn pack(self) -> Result<wrappers::$wrapper> {
+          Ok(wrappers::$wrapper { value: self })
+        }
+      }
+
+      impl ProtoPackRef<wrappers::$wrapper> for $ty {
+        fn pack_ref(&self) -> Result<wrappers::$wrapper> {
+          Ok(wrappers::$wrapper {
+            value: self.clone(),
+          })
+        }
+      }
+
+      impl ProtoUnpack<wrappers::$wrapper> for $ty {
+        fn unpack(value: wrappers::$wrapper) -> Result<$ty> {
+          Ok(value.value)
+        }
+      }
+
+      impl_option!($ty => wrappers::$wrapper);
+    )*
+  }
+}
+
+impl_wrapper! {
+  f32 => FloatValue,
+  f64 => DoubleValue,
+  i64 => Int64Value,
+  u64 => UInt64Value,
+  i32 => Int32Value,
+  u32 => UInt32Value,
+  bool => BoolValue,
+  String => StringValue,
+  Vec<u8> => BytesValue
+}
+
 // repeated value
 
 impl<T, T2> ProtoPack<Vec<T>> for Vec<T2>
@@ -297,6 +452,23 @@ where
   }
 }
 
+impl<T, T2> ProtoPackRef<Vec<T>> for Vec<T2>
+where
+  T2: ProtoPackRef<T>,
+{
+  fn pack_ref(&self) -> Result<Vec<T>> {
+    let mut r = vec![];
+    for (i, elem) in self.iter().enumerate() {
+      let item = elem.pack_ref().map_err(|e| result::Error::ListElement {
+        source: Box::new(e),
+        index: i,
+      })?;
+      r.push(item);
+    }
+    Ok(r)
+  }
+}
+
 impl<T, T2> ProtoUnpack<Vec<T>> for Vec<T2>
 where
   T2: ProtoUnpack<T>,
@@ -312,48 +484,333 @@ where
     }
     Ok(r)
   }
+
+  fn unpack_all_errors(value: Vec<T>) -> Result<Vec<T2>, Errors> {
+    let mut r = vec![];
+    let mut errors = vec![];
+    for (i, elem) in value.into_iter().enumerate() {
+      match T2::unpack_all_errors(elem) {
+        Ok(item) => r.push(item),
+        Err(errs) => errors.extend(errs.into_iter().map(|e| result::Error::ListElement {
+          source: Box::new(e),
+          index: i,
+        })),
+      }
+    }
+    if errors.is_empty() {
+      Ok(r)
+    } else {
+      Err(errors.into())
+    }
+  }
 }
 
 // map
 
-impl<K, V, K2, V2> ProtoPack<HashMap<K, V>> for HashMap<K2, V2>
+macro_rules! impl_map {
+  (
+    $($rust:ident [$($rust_bound:tt)+] => $proto:ident [$($proto_bound:tt)+]),*
+  ) => {
+    $(
+      impl<K, V, K2, V2> ProtoPack<$proto<K, V>> for $rust<K2, V2>
+      where
+        K: Debug + $($proto_bound)+,
+        K2: ProtoPack<K> + $($rust_bound)+,
+        V2: ProtoPack<V>,
+      {
+        fn pack(self) -> Result<$proto<K, V>> {
+          let mut r = $proto::new();
+          for (k, v) in self.into_iter() {
+            let k2 = k.pack().map_err(|e| result::Error::MapEntry {
+              source: Box::new(e),
+              key: None,
+              part: MapEntryPart::Key,
+            })?;
+            let v2 = v.pack().map_err(|e| result::Error::MapEntry {
+              source: Box::new(e),
+              key: Some(format!("{:?}", k2)),
+              part: MapEntryPart::Value,
+            })?;
+            if r.contains_key(&k2) {
+              return Err(result::Error::DuplicateMapKey {
+                key: format!("{:?}", k2),
+              });
+            }
+            r.insert(k2, v2);
+          }
+          Ok(r)
+        }
+      }
+
+      impl<K, V, K2, V2> ProtoPackRef<$proto<K, V>> for $rust<K2, V2>
+      where
+        K: Debug + $($proto_bound)+,
+        K2: ProtoPackRef<K> + Debug + $($rust_bound)+,
+        V2: ProtoPackRef<V>,
+      {
+        fn pack_ref(&self) -> Result<$proto<K, V>> {
+          let mut r = $proto::new();
+          for (k, v) in self.iter() {
+            let k2 = k.pack_ref().map_err(|e| result::Error::MapEntry {
+              source: Box::new(e),
+              key: Some(format!("{:?}", k)),
+              part: MapEntryPart::Key,
+            })?;
+            let v2 = v.pack_ref().map_err(|e| result::Error::MapEntry {
+              source: Box::new(e),
+              key: Some(format!("{:?}", k2)),
+              part: MapEntryPart::Value,
+            })?;
+            if r.contains_key(&k2) {
+              return Err(result::Error::DuplicateMapKey {
+                key: format!("{:?}", k2),
+              });
+            }
+            r.insert(k2, v2);
+          }
+          Ok(r)
+        }
+      }
+
+      impl<K, V, K2, V2> ProtoUnpack<$proto<K, V>> for $rust<K2, V2>
+      where
+        K: Clone + Debug + $($proto_bound)+,
+        K2: ProtoUnpack<K> + Debug + $($rust_bound)+,
+        V2: ProtoUnpack<V>,
+      {
+        fn unpack(value: $proto<K, V>) -> Result<$rust<K2, V2>> {
+          let mut r = $rust::new();
+          for (k, v) in value.into_iter() {
+            let v2 = V2::unpack(v).map_err(|e| result::Error::MapEntry {
+              source: Box::new(e),
+              key: Some(format!("{:?}", k)),
+              part: MapEntryPart::Value,
+            })?;
+            let k2 = K2::unpack(k.clone()).map_err(|e| result::Error::MapEntry {
+              source: Box::new(e),
+              key: Some(format!("{:?}", k)),
+              part: MapEntryPart::Key,
+            })?;
+            if r.contains_key(&k2) {
+              return Err(result::Error::DuplicateMapKey {
+                key: format!("{:?}", k2),
+              });
+            }
+            r.insert(k2, v2);
+          }
+          Ok(r)
+        }
+
+        fn unpack_all_errors(value: $proto<K, V>) -> Result<$rust<K2, V2>, Errors> {
+          let mut r = $rust::new();
+          let mut errors = vec![];
+          for (k, v) in value.into_iter() {
+            let v2 = V2::unpack_all_errors(v);
+            let k2 = K2::unpack_all_errors(k.clone());
+            let key = (k2.is_err() || v2.is_err()).then(|| format!("{:?}", k));
+            match (k2, v2) {
+              (Ok(k2), Ok(_)) if r.contains_key(&k2) => {
+                errors.push(result::Error::DuplicateMapKey {
+                  key: format!("{:?}", k2),
+                });
+              }
+              (Ok(k2), Ok(v2)) => {
+                r.insert(k2, v2);
+              }
+              (k2, v2) => {
+                let parts = k2
+                  .err()
+                  .map(|errs| (MapEntryPart::Key, errs))
+                  .into_iter()
+                  .chain(v2.err().map(|errs| (MapEntryPart::Value, errs)));
+                for (part, errs) in parts {
+                  errors.extend(errs.into_iter().map(|e| result::Error::MapEntry {
+                    source: Box::new(e),
+                    key: key.clone(),
+                    part,
+                  }));
+                }
+              }
+            }
+          }
+          if errors.is_empty() {
+            Ok(r)
+          } else {
+            Err(errors.into())
+          }
+        }
+      }
+    )*
+  };
+}
+
+impl_map! {
+  HashMap [Eq + Hash] => HashMap [Eq + Hash],
+  HashMap [Eq + Hash] => BTreeMap [Ord],
+  BTreeMap [Ord] => HashMap [Eq + Hash],
+  BTreeMap [Ord] => BTreeMap [Ord]
+}
+
+#[cfg(feature = "indexmap")]
+impl_map! {
+  IndexMap [Eq + Hash] => HashMap [Eq + Hash],
+  IndexMap [Eq + Hash] => BTreeMap [Ord],
+  IndexMap [Eq + Hash] => IndexMap [Eq + Hash],
+  HashMap [Eq + Hash] => IndexMap [Eq + Hash],
+  BTreeMap [Ord] => IndexMap [Eq + Hash]
+}
+
+// set
+
+macro_rules! impl_set {
+  (
+    $($rust:ident [$($rust_bound:tt)+]),*
+  ) => {
+    $(
+      impl<T, T2> ProtoPack<Vec<T>> for $rust<T2>
+      where
+        T2: ProtoPack<T> + $($rust_bound)+,
+      {
+        fn pack(self) -> Result<Vec<T>> {
+          let mut r = vec![];
+          for (i, elem) in self.into_iter().enumerate() {
+            let item = elem.pack().map_err(|e| result::Error::ListElement {
+              source: Box::new(e),
+              index: i,
+            })?;
+            r.push(item);
+          }
+          Ok(r)
+        }
+      }
+
+      impl<T, T2> ProtoPackRef<Vec<T>> for $rust<T2>
+      where
+        T2: ProtoPackRef<T> + $($rust_bound)+,
+      {
+        fn pack_ref(&self) -> Result<Vec<T>> {
+          let mut r = vec![];
+          for (i, elem) in self.iter().enumerate() {
+            let item = elem.pack_ref().map_err(|e| result::Error::ListElement {
+              source: Box::new(e),
+              index: i,
+            })?;
+            r.push(item);
+          }
+          Ok(r)
+        }
+      }
+
+      impl<T, T2> ProtoUnpack<Vec<T>> for $rust<T2>
+      where
+        T2: ProtoUnpack<T> + $($rust_bound)+,
+      {
+        fn unpack(value: Vec<T>) -> Result<$rust<T2>> {
+          let mut r = $rust::new();
+          for (i, elem) in value.into_iter().enumerate() {
+            let item = T2::unpack(elem).map_err(|e| result::Error::ListElement {
+              source: Box::new(e),
+              index: i,
+            })?;
+            if !r.insert(item) {
+              return Err(result::Error::ListElement {
+                source: Box::new(result::Error::DuplicateSetElement),
+                index: i,
+              });
+            }
+          }
+          Ok(r)
+        }
+
+        fn unpack_all_errors(value: Vec<T>) -> Result<$rust<T2>, Errors> {
+          let mut r = $rust::new();
+          let mut errors = vec![];
+          for (i, elem) in value.into_iter().enumerate() {
+            match T2::unpack_all_errors(elem) {
+              Ok(item) => {
+                if !r.insert(item) {
+                  errors.push(result::Error::ListElement {
+                    source: Box::new(result::Error::DuplicateSetElement),
+                    index: i,
+                  });
+                }
+              }
+              Err(errs) => errors.extend(errs.into_iter().map(|e| result::Error::ListElement {
+                source: Box::new(e),
+                index: i,
+              })),
+            }
+          }
+          if errors.is_empty() {
+            Ok(r)
+          } else {
+            Err(errors.into())
+          }
+        }
+      }
+    )*
+  };
+}
+
+impl_set! {
+  HashSet [Eq + Hash],
+  BTreeSet [Ord]
+}
+
+macro_rules! impl_pointer {
+  ($($ptr:ident),*) => {
+    $(
+      impl<T, T2> ProtoPackRef<T> for $ptr<T2>
+      where
+        T2: ProtoPackRef<T>,
+      {
+        fn pack_ref(&self) -> Result<T> {
+          (**self).pack_ref()
+        }
+      }
+
+      impl<T, T2> ProtoUnpack<T> for $ptr<T2>
+      where
+        T2: ProtoUnpack<T>,
+      {
+        fn unpack(value: T) -> Result<Self> {
+          T2::unpack(value).map($ptr::new)
+        }
+
+        fn unpack_all_errors(value: T) -> Result<Self, Errors> {
+          T2::unpack_all_errors(value).map($ptr::new)
+        }
+      }
+    )*
+  };
+}
+
+impl_pointer!(Box, Arc, Rc);
+
+impl<T, T2> ProtoPack<T> for Box<T2>
 where
-  K: Eq + Hash,
-  K2: ProtoPack<K> + Eq + Hash,
-  V2: ProtoPack<V>,
+  T2: ProtoPack<T>,
 {
-  fn pack(self) -> Result<HashMap<K, V>> {
-    let mut r = vec![];
-    for (k, v) in self.into_iter() {
-      let k2 = k.pack().map_err(|e| result::Error::MapEntry {
-        source: Box::new(e),
-      })?;
-      let v2 = v.pack().map_err(|e| result::Error::MapEntry {
-        source: Box::new(e),
-      })?;
-      r.push((k2, v2));
-    }
-    Ok(r.into_iter().collect())
+  fn pack(self) -> Result<T> {
+    (*self).pack()
   }
 }
 
-impl<K, V, K2, V2> ProtoUnpack<HashMap<K, V>> for HashMap<K2, V2>
+/// Shared pointers can't move their value out, so they pack through `ProtoPackRef`.
+impl<T, T2> ProtoPack<T> for Arc<T2>
 where
-  K: Eq + Hash,
-  K2: ProtoUnpack<K> + Eq + Hash,
-  V2: ProtoUnpack<V>,
+  T2: ProtoPackRef<T>,
 {
-  fn unpack(value: HashMap<K, V>) -> Result<HashMap<K2, V2>> {
-    let mut r = vec![];
-    for (k, v) in value.into_iter() {
-      let k2 = K2::unpack(k).map_err(|e| result::Error::MapEntry {
-        source: Box::new(e),
-      })?;
-      let v2 = V2::unpack(v).map_err(|e| result::Error::MapEntry {
-        source: Box::new(e),
-      })?;
-      r.push((k2, v2));
-    }
-    Ok(r.into_iter().collect())
+  fn pack(self) -> Result<T> {
+    (*self).pack_ref()
+  }
+}
+
+impl<T, T2> ProtoPack<T> for Rc<T2>
+where
+  T2: ProtoPackRef<T>,
+{
+  fn pack(self) -> Result<T> {
+    (*self).pack_ref()
   }
 }
@@ -1,7 +1,11 @@
 mod convert;
+pub mod map_last_wins;
+pub mod proto_enum;
 pub mod result;
+pub mod validate;
+pub mod wrappers;
 
-use crate::result::Error;
+use crate::result::{Error, Errors};
 
 pub use self::convert::Json;
 pub use protobuf_mapper_codegen::*;
@@ -13,11 +17,30 @@ where
   fn pack(self) -> Result<T, Error>;
 }
 
+/// Packs a borrowed value, cloning only the leaf values copied into the message.
+pub trait ProtoPackRef<T> {
+  fn pack_ref(&self) -> Result<T, Error>;
+}
+
+impl<T, U> ProtoPack<T> for &U
+where
+  U: ProtoPackRef<T> + ?Sized,
+{
+  fn pack(self) -> Result<T, Error> {
+    self.pack_ref()
+  }
+}
+
 pub trait ProtoUnpack<T>
 where
   Self: Sized,
 {
   fn unpack(value: T) -> Result<Self, Error>;
+
+  /// Like `unpack`, but keeps going after the first failure and returns every error found.
+  fn unpack_all_errors(value: T) -> Result<Self, Errors> {
+    Self::unpack(value).map_err(Into::into)
+  }
 }
 
 pub trait ProtoEnumMeta {
@@ -25,21 +48,76 @@ pub trait ProtoEnumMeta {
   fn get_variant_name(&self) -> &'static str;
 }
 
+pub trait ProtoEnumVariants
+where
+  Self: Sized + 'static,
+{
+  /// Every variant except `other`, in declaration order.
+  const VARIANTS: &'static [Self];
+  /// Inverse of `ProtoEnumMeta::get_variant_name`.
+  fn from_variant_name(name: &str) -> Option<Self>;
+}
+
+/// Names of the values of the proto enum `T` in the `.proto` file.
+pub trait ProtoEnumName<T>
+where
+  Self: Sized,
+{
+  /// e.g. `ORDER_STATUS_SHIPPED`, or `None` for the `other` variant, which `T` can't hold.
+  fn proto_name(&self) -> Option<&'static str>;
+  /// Inverse of `proto_name`.
+  fn from_proto_name(name: &str) -> Option<Self>;
+}
+
 pub trait ProtoEnum<T>
 where
   Self: Sized,
 {
   fn from_i32(v: i32) -> Option<Self>;
+  /// Gives `T`'s default value for the `other` variant, which `T` can't hold.
   fn into_proto_enum(self) -> T;
-  fn unpack_i32(v: i32) -> Result<Self, Error> where Self: ProtoEnumMeta{
-    Self::from_i32(v).ok_or_else(|| Error::EnumDiscriminantNotFound {
+  /// Defaults to `into_proto_enum` on a clone.
+  fn to_proto_enum(&self) -> T
+  where
+    Self: Clone,
+  {
+    self.clone().into_proto_enum()
+  }
+  /// Like `into_proto_enum`, but fails with `Error::EnumDiscriminantNotFound` for the `other`
+  /// variant.
+  fn try_into_proto_enum(self) -> Result<T, Error> {
+    Ok(self.into_proto_enum())
+  }
+  /// Defaults to `try_into_proto_enum` on a clone.
+  fn try_to_proto_enum(&self) -> Result<T, Error>
+  where
+    Self: Clone,
+  {
+    self.clone().try_into_proto_enum()
+  }
+  /// Like `into_proto_enum`, but keeps the raw discriminant of the `other` variant.
+  fn pack_i32(self) -> Result<i32, Error>
+  where
+    T: Into<i32>,
+  {
+    Ok(self.into_proto_enum().into())
+  }
+  fn unpack_i32(v: i32) -> Result<Self, Error>
+  where
+    Self: ProtoEnumMeta,
+  {
+    Self::from_i32(v).ok_or(Error::EnumDiscriminantNotFound {
       enum_name: Self::NAME,
-      discriminant: v
+      discriminant: v,
     })
   }
-  fn unpack_enum(v: T) -> Self;
+  /// Fails with `Error::EnumUnspecified` for the `unspecified` value of the enum.
+  fn unpack_enum(v: T) -> Result<Self, Error>;
 }
 
+/// Marks an enum derived with `unspecified`, whose `Option` maps the unspecified value to `None`.
+pub trait ProtoEnumUnspecified {}
+
 impl<T1, T2> ProtoPack<Option<T1>> for Option<T2>
 where
   T2: ProtoPack<T1>,
@@ -53,6 +131,19 @@ where
   }
 }
 
+impl<T1, T2> ProtoPackRef<Option<T1>> for Option<T2>
+where
+  T2: ProtoPackRef<T1>,
+{
+  fn pack_ref(&self) -> Result<Option<T1>, Error> {
+    if let Some(value) = self {
+      Ok(Some(value.pack_ref()?))
+    } else {
+      Ok(None)
+    }
+  }
+}
+
 impl<T1, T2> ProtoUnpack<Option<T1>> for Option<T2>
 where
   T2: ProtoUnpack<T1>,
@@ -64,4 +155,53 @@ where
       Ok(None)
     }
   }
+
+  fn unpack_all_errors(value: Option<T1>) -> Result<Self, Errors> {
+    if let Some(value) = value {
+      Ok(Some(T2::unpack_all_errors(value)?))
+    } else {
+      Ok(None)
+    }
+  }
+}
+
+/// Maps the `unspecified` value of an enum to `None`. `None` packs to `0`, which proto3
+/// reserves for it.
+impl<T> ProtoPack<i32> for Option<T>
+where
+  T: ProtoPack<i32> + ProtoEnumUnspecified,
+{
+  fn pack(self) -> Result<i32, Error> {
+    if let Some(value) = self {
+      value.pack()
+    } else {
+      Ok(0)
+    }
+  }
+}
+
+impl<T> ProtoPackRef<i32> for Option<T>
+where
+  T: ProtoPackRef<i32> + ProtoEnumUnspecified,
+{
+  fn pack_ref(&self) -> Result<i32, Error> {
+    if let Some(value) = self {
+      value.pack_ref()
+    } else {
+      Ok(0)
+    }
+  }
+}
+
+impl<T> ProtoUnpack<i32> for Option<T>
+where
+  T: ProtoUnpack<i32> + ProtoEnumUnspecified,
+{
+  fn unpack(value: i32) -> Result<Self, Error> {
+    match T::unpack(value) {
+      Ok(value) => Ok(Some(value)),
+      Err(Error::EnumUnspecified { .. }) => Ok(None),
+      Err(err) => Err(err),
+    }
+  }
 }
@@ -0,0 +1,82 @@
+//! Map conversions that keep the last entry when two keys convert to the same key, instead of
+//! failing with `Error::DuplicateMapKey`.
+//!
+//! "Last" follows the iteration order of the source map, so the kept entry is only predictable
+//! when that order is: a `BTreeMap` or an `IndexMap`. With a `HashMap` source any of the
+//! colliding entries may win.
+//!
+//! The derives use these for fields marked with `#[protobuf_mapper(map_last_wins)]`.
+
+use crate::result::{Error, MapEntryPart, Result};
+use std::fmt::Debug;
+use crate::{ProtoPack, ProtoPackRef, ProtoUnpack};
+
+pub fn pack<M, K, V, K2, V2, R>(map: M) -> Result<R>
+where
+  M: IntoIterator<Item = (K2, V2)>,
+  K: Debug,
+  K2: ProtoPack<K>,
+  V2: ProtoPack<V>,
+  R: FromIterator<(K, V)>,
+{
+  map
+    .into_iter()
+    .map(|(k, v)| {
+      let k = k.pack().map_err(|e| key_error::<K>(e, None))?;
+      let v = v.pack().map_err(|e| value_error(e, &k))?;
+      Ok((k, v))
+    })
+    .collect()
+}
+
+pub fn pack_ref<'a, M, K, V, K2, V2, R>(map: &'a M) -> Result<R>
+where
+  &'a M: IntoIterator<Item = (&'a K2, &'a V2)>,
+  K: Debug,
+  K2: ProtoPackRef<K> + Debug + 'a,
+  V2: ProtoPackRef<V> + 'a,
+  R: FromIterator<(K, V)>,
+{
+  map
+    .into_iter()
+    .map(|(k, v)| {
+      let k = k.pack_ref().map_err(|e| key_error(e, Some(k)))?;
+      let v = v.pack_ref().map_err(|e| value_error(e, &k))?;
+      Ok((k, v))
+    })
+    .collect()
+}
+
+pub fn unpack<M, K, V, K2, V2, R>(map: M) -> Result<R>
+where
+  M: IntoIterator<Item = (K, V)>,
+  K: Clone + Debug,
+  K2: ProtoUnpack<K>,
+  V2: ProtoUnpack<V>,
+  R: FromIterator<(K2, V2)>,
+{
+  map
+    .into_iter()
+    .map(|(k, v)| {
+      let v = V2::unpack(v).map_err(|e| value_error(e, &k))?;
+      let k = K2::unpack(k.clone()).map_err(|e| key_error(e, Some(&k)))?;
+      Ok((k, v))
+    })
+    .collect()
+}
+
+fn key_error<K: Debug>(source: Error, key: Option<&K>) -> Error {
+  Error::MapEntry {
+    source: Box::new(source),
+    key: key.map(|key| format!("{:?}", key)),
+    part: MapEntryPart::Key,
+  }
+}
+
+fn value_error<K: Debug>(source: Error, key: &K) -> Error {
+  Error::MapEntry {
+    source: Box::new(source),
+    key: Some(format!("{:?}", key)),
+    part: MapEntryPart::Value,
+  }
+}
@@ -0,0 +1,178 @@
+//! Conversions for `#[protobuf_mapper(proto_enum, strict)]` fields, which go through the raw `i32`
+//! values rather than prost's setter and getter. Those don't exist for enums inside a `Vec`, a map
+//! or an `Option`, and the getter hides unknown values.
+
+use crate::result::{Error, Errors, MapEntryPart, Result};
+use crate::{ProtoEnum, ProtoEnumMeta};
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// A Rust field holding `ProtoEnum<P>` values, stored as `R` in the message.
+pub trait EnumField<P, R>: Sized {
+  fn pack(self) -> Result<R>;
+  /// Packs a clone, enum values are cheap to copy.
+  fn pack_ref(&self) -> Result<R>
+  where
+    Self: Clone,
+  {
+    self.clone().pack()
+  }
+  fn unpack(value: R) -> Result<Self>;
+
+  /// Like `unpack`, but keeps going after the first failure and returns every error found.
+  fn unpack_all_errors(value: R) -> Result<Self, Errors> {
+    Self::unpack(value).map_err(Into::into)
+  }
+}
+
+impl<E, P> EnumField<P, i32> for E
+where
+  E: ProtoEnum<P> + ProtoEnumMeta,
+  P: Into<i32>,
+{
+  fn pack(self) -> Result<i32> {
+    self.pack_i32()
+  }
+
+  fn unpack(value: i32) -> Result<Self> {
+    E::unpack_i32(value)
+  }
+}
+
+impl<E, P> EnumField<P, Option<i32>> for Option<E>
+where
+  E: EnumField<P, i32>,
+{
+  fn pack(self) -> Result<Option<i32>> {
+    self.map(E::pack).transpose()
+  }
+
+  fn unpack(value: Option<i32>) -> Result<Self> {
+    value.map(E::unpack).transpose()
+  }
+}
+
+impl<E, P> EnumField<P, Vec<i32>> for Vec<E>
+where
+  E: EnumField<P, i32>,
+{
+  fn pack(self) -> Result<Vec<i32>> {
+    self
+      .into_iter()
+      .enumerate()
+      .map(|(index, v)| {
+        v.pack().map_err(|e| Error::ListElement {
+          source: Box::new(e),
+          index,
+        })
+      })
+      .collect()
+  }
+
+  fn unpack(value: Vec<i32>) -> Result<Self> {
+    value
+      .into_iter()
+      .enumerate()
+      .map(|(index, v)| {
+        E::unpack(v).map_err(|e| Error::ListElement {
+          source: Box::new(e),
+          index,
+        })
+      })
+      .collect()
+  }
+
+  fn unpack_all_errors(value: Vec<i32>) -> Result<Self, Errors> {
+    let mut r = vec![];
+    let mut errors = vec![];
+    for (index, v) in value.into_iter().enumerate() {
+      match E::unpack(v) {
+        Ok(item) => r.push(item),
+        Err(e) => errors.push(Error::ListElement {
+          source: Box::new(e),
+          index,
+        }),
+      }
+    }
+    if errors.is_empty() {
+      Ok(r)
+    } else {
+      Err(errors.into())
+    }
+  }
+}
+
+macro_rules! impl_map {
+  ($($map:ident [$($bounds:tt)*]),*) => {
+    $(
+      impl<K, E, P> EnumField<P, $map<K, i32>> for $map<K, E>
+      where
+        K: Debug + $($bounds)*,
+        E: EnumField<P, i32>,
+      {
+        fn pack(self) -> Result<$map<K, i32>> {
+          self
+            .into_iter()
+            .map(|(k, v)| {
+              let v = v.pack().map_err(|e| Error::MapEntry {
+                source: Box::new(e),
+                key: Some(format!("{:?}", k)),
+                part: MapEntryPart::Value,
+              })?;
+              Ok((k, v))
+            })
+            .collect()
+        }
+
+        fn unpack(value: $map<K, i32>) -> Result<Self> {
+          value
+            .into_iter()
+            .map(|(k, v)| {
+              let v = E::unpack(v).map_err(|e| Error::MapEntry {
+                source: Box::new(e),
+                key: Some(format!("{:?}", k)),
+                part: MapEntryPart::Value,
+              })?;
+              Ok((k, v))
+            })
+            .collect()
+        }
+
+        fn unpack_all_errors(value: $map<K, i32>) -> Result<Self, Errors> {
+          let mut r = $map::new();
+          let mut errors = vec![];
+          for (k, v) in value.into_iter() {
+            match E::unpack(v) {
+              Ok(v) => {
+                r.insert(k, v);
+              }
+              Err(e) => errors.push(Error::MapEntry {
+                source: Box::new(e),
+                key: Some(format!("{:?}", k)),
+                part: MapEntryPart::Value,
+              }),
+            }
+          }
+          if errors.is_empty() {
+            Ok(r)
+          } else {
+            Err(errors.into())
+          }
+        }
+      }
+    )*
+  };
+}
+
+impl_map! {
+  HashMap [Eq + Hash],
+  BTreeMap [Ord]
+}
+
+#[cfg(feature = "indexmap")]
+impl_map! {
+  IndexMap [Eq + Hash]
+}
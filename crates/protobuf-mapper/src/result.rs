@@ -1,3 +1,4 @@
+use std::fmt;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,14 +11,38 @@ pub enum Error {
   ValueNotPresent,
   #[error("Could not unpack field '{field_name}' from null")]
   FieldValueNotPresent { field_name: &'static str },
+  #[error("Could not unpack a non-optional oneof from null")]
+  OneofNotPresent,
+  #[error("Could not unpack oneof field '{field_name}' from null")]
+  OneofValueNotPresent { field_name: &'static str },
   #[error("JSON value nested too deeply")]
   JsonValueNestedTooDeeply,
-  #[error("List element {index}: {source}")]
+  #[error("{}: {}", format_path(&self.path()), self.leaf())]
+  Field {
+    source: Box<Error>,
+    field_name: &'static str,
+  },
+  #[error("{}: {}", format_path(&self.path()), self.leaf())]
   ListElement { source: Box<Error>, index: usize },
-  #[error("Map entry: {}", source)]
-  MapEntry { source: Box<Error> },
+  #[error("{}: {}", format_path(&self.path()), self.leaf())]
+  MapEntry {
+    source: Box<Error>,
+    key: Option<String>,
+    part: MapEntryPart,
+  },
+  #[error("Duplicate map key: {key}")]
+  DuplicateMapKey { key: String },
+  #[error("Duplicate set element")]
+  DuplicateSetElement,
+  #[error("{}", format_validation(*field, message))]
+  Validation {
+    field: Option<&'static str>,
+    message: String,
+  },
   #[error("Parse decimal error: {0}")]
   ParseBigDecimal(#[from] bigdecimal::ParseBigDecimalError),
+  #[error("Timestamp is out of range: seconds = {seconds}, nanos = {nanos}")]
+  TimestampOutOfRange { seconds: i64, nanos: i32 },
   #[error("Parse duration error: {message}")]
   ParseDuration { message: String },
   #[error(
@@ -29,6 +54,214 @@ pub enum Error {
     enum_name: &'static str,
     discriminant: i32,
   },
+  #[error("Enum value is unspecified: enum type = {enum_name}")]
+  EnumUnspecified { enum_name: &'static str },
+  #[error("Enum name is not found: enum type = {enum_name}, name = {name}")]
+  EnumNameNotFound {
+    enum_name: &'static str,
+    name: String,
+  },
+}
+
+impl Error {
+  /// Attaches `field_name` to an error returned while unpacking a field.
+  pub fn field(field_name: &'static str, source: Error) -> Error {
+    match source {
+      Error::ValueNotPresent => Error::FieldValueNotPresent { field_name },
+      Error::OneofNotPresent => Error::OneofValueNotPresent { field_name },
+      Error::Validation {
+        field: None,
+        message,
+      } => Error::Validation {
+        field: Some(field_name),
+        message,
+      },
+      source => Error::Field {
+        source: Box::new(source),
+        field_name,
+      },
+    }
+  }
+
+  /// Whether the value itself was absent, as opposed to failing to convert.
+  pub fn is_value_not_present(&self) -> bool {
+    matches!(
+      self,
+      Error::ValueNotPresent
+        | Error::OneofNotPresent
+        | Error::OneofValueNotPresent { .. }
+    )
+  }
+
+  /// Location of the failing value, from the outermost field to the innermost.
+  pub fn path(&self) -> Vec<PathSegment> {
+    let mut path = vec![];
+    let mut err = self;
+    loop {
+      match err {
+        Error::Field { source, field_name } => {
+          path.push(PathSegment::Field(field_name));
+          err = source;
+        }
+        Error::ListElement { source, index } => {
+          path.push(PathSegment::Index(*index));
+          err = source;
+        }
+        Error::MapEntry { source, key, part } => {
+          path.push(PathSegment::MapEntry {
+            key: key.clone(),
+            part: *part,
+          });
+          err = source;
+        }
+        Error::FieldValueNotPresent { field_name }
+        | Error::OneofValueNotPresent { field_name }
+        | Error::Validation {
+          field: Some(field_name),
+          ..
+        } => {
+          path.push(PathSegment::Field(field_name));
+          break;
+        }
+        _ => break,
+      }
+    }
+    path
+  }
+
+  /// The innermost error, with all path information stripped.
+  pub fn leaf(&self) -> &Error {
+    match self {
+      Error::Field { source, .. }
+      | Error::ListElement { source, .. }
+      | Error::MapEntry { source, .. } => source.leaf(),
+      err => err,
+    }
+  }
+}
+
+fn format_validation(field: Option<&str>, message: &str) -> String {
+  match field {
+    Some(field) => format!("Invalid value for field '{}': {}", field, message),
+    None => format!("Invalid value: {}", message),
+  }
+}
+
+/// Which half of a map entry failed to convert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapEntryPart {
+  Key,
+  Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+  Field(&'static str),
+  Index(usize),
+  /// `key` is the `Debug` rendering of the protobuf side key, or of the Rust side key when it
+  /// failed to pack by reference. It is `None` when a key failed to pack by value, because the
+  /// conversion consumed it; the source error describes the key instead.
+  MapEntry {
+    key: Option<String>,
+    part: MapEntryPart,
+  },
+}
+
+impl fmt::Display for PathSegment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PathSegment::Field(name) => write!(f, ".{}", name),
+      PathSegment::Index(index) => write!(f, "[{}]", index),
+      PathSegment::MapEntry { key, part } => {
+        let key = key.as_ref().map(String::as_str).unwrap_or("_");
+        match part {
+          MapEntryPart::Key => write!(f, "[key: {}]", key),
+          MapEntryPart::Value => write!(f, "[{}]", key),
+        }
+      }
+    }
+  }
+}
+
+/// Renders a path like `order.items[3].price`.
+pub fn format_path(path: &[PathSegment]) -> String {
+  let mut out = String::new();
+  for segment in path {
+    out.push_str(&segment.to_string());
+  }
+  out.trim_start_matches('.').to_string()
+}
+
+/// Every error collected by `ProtoUnpack::unpack_all_errors`.
+#[derive(Debug)]
+pub struct Errors(Vec<Error>);
+
+impl Errors {
+  pub fn errors(&self) -> &[Error] {
+    &self.0
+  }
+
+  pub fn into_vec(self) -> Vec<Error> {
+    self.0
+  }
+
+  /// Whether the only error is an absent value, see `Error::is_value_not_present`.
+  pub fn is_value_not_present(&self) -> bool {
+    matches!(self.0.as_slice(), [err] if err.is_value_not_present())
+  }
+
+  /// One violation per error, in the shape of gRPC's `BadRequest.FieldViolation`.
+  pub fn field_violations(&self) -> Vec<FieldViolation> {
+    self
+      .0
+      .iter()
+      .map(|err| FieldViolation {
+        field: format_path(&err.path()),
+        description: err.leaf().to_string(),
+      })
+      .collect()
+  }
+}
+
+impl From<Error> for Errors {
+  fn from(err: Error) -> Self {
+    Errors(vec![err])
+  }
+}
+
+impl From<Vec<Error>> for Errors {
+  fn from(errors: Vec<Error>) -> Self {
+    Errors(errors)
+  }
+}
+
+impl IntoIterator for Errors {
+  type Item = Error;
+  type IntoIter = std::vec::IntoIter<Error>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.0.into_iter()
+  }
+}
+
+impl fmt::Display for Errors {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, err) in self.0.iter().enumerate() {
+      if i > 0 {
+        write!(f, "; ")?;
+      }
+      write!(f, "{}", err)?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for Errors {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldViolation {
+  pub field: String,
+  pub description: String,
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
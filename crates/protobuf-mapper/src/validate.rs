@@ -0,0 +1,139 @@
+//! Checks behind the declarative field validators, e.g. `#[protobuf_mapper(min_len = 1)]`.
+//!
+//! Each check returns `Error::Validation` without a field name; the derive attaches it.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
+#[cfg(feature = "regex")]
+pub use regex::Regex;
+
+use crate::result::{Error, Result};
+
+/// Values with a length. Strings are measured in characters.
+pub trait Len {
+  fn len(&self) -> usize;
+
+  fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl Len for String {
+  fn len(&self) -> usize {
+    self.chars().count()
+  }
+}
+
+impl<T> Len for Vec<T> {
+  fn len(&self) -> usize {
+    Vec::len(self)
+  }
+}
+
+macro_rules! impl_len {
+  ($($ty:ident <$($param:ident),*>),*) => {
+    $(
+      impl<$($param),*> Len for $ty<$($param),*> {
+        fn len(&self) -> usize {
+          $ty::len(self)
+        }
+      }
+    )*
+  };
+}
+
+impl_len!(HashMap<K, V>, BTreeMap<K, V>, HashSet<T>, BTreeSet<T>);
+
+#[cfg(feature = "indexmap")]
+impl_len!(IndexMap<K, V>);
+
+fn invalid(message: String) -> Error {
+  Error::Validation {
+    field: None,
+    message,
+  }
+}
+
+/// Fails with `Error::ValueNotPresent` if the value equals its default, which is how proto3
+/// encodes an unset scalar, string or collection.
+pub fn required<T>(value: &T) -> Result<()>
+where
+  T: Default + PartialEq,
+{
+  if *value == T::default() {
+    Err(Error::ValueNotPresent)
+  } else {
+    Ok(())
+  }
+}
+
+pub fn non_empty<T>(value: &T) -> Result<()>
+where
+  T: Len + ?Sized,
+{
+  if value.is_empty() {
+    Err(invalid("must not be empty".to_string()))
+  } else {
+    Ok(())
+  }
+}
+
+pub fn min_len<T>(value: &T, min: usize) -> Result<()>
+where
+  T: Len + ?Sized,
+{
+  if value.len() < min {
+    Err(invalid(format!("length must be at least {}", min)))
+  } else {
+    Ok(())
+  }
+}
+
+pub fn max_len<T>(value: &T, max: usize) -> Result<()>
+where
+  T: Len + ?Sized,
+{
+  if value.len() > max {
+    Err(invalid(format!("length must be at most {}", max)))
+  } else {
+    Ok(())
+  }
+}
+
+/// Values that don't compare with `min`, like NaN, fail too.
+pub fn min<T>(value: &T, min: T) -> Result<()>
+where
+  T: PartialOrd + Display,
+{
+  match value.partial_cmp(&min) {
+    Some(Ordering::Equal) | Some(Ordering::Greater) => Ok(()),
+    _ => Err(invalid(format!("must be at least {}", min))),
+  }
+}
+
+/// Values that don't compare with `max`, like NaN, fail too.
+pub fn max<T>(value: &T, max: T) -> Result<()>
+where
+  T: PartialOrd + Display,
+{
+  match value.partial_cmp(&max) {
+    Some(Ordering::Equal) | Some(Ordering::Less) => Ok(()),
+    _ => Err(invalid(format!("must be at most {}", max))),
+  }
+}
+
+#[cfg(feature = "regex")]
+pub fn pattern<T>(value: &T, regex: &Regex) -> Result<()>
+where
+  T: AsRef<str> + ?Sized,
+{
+  if regex.is_match(value.as_ref()) {
+    Ok(())
+  } else {
+    Err(invalid(format!("must match pattern '{}'", regex.as_str())))
+  }
+}
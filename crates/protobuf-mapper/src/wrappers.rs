@@ -0,0 +1,59 @@
+//! Message types for the `google/protobuf/wrappers.proto` well-known types.
+//!
+//! prost maps these to `Option<T>` by default, so the types here are only needed when the
+//! wrappers are compiled as messages, e.g. with `prost_build::Config::compile_well_known_types`
+//! combined with `extern_path(".google.protobuf", "::protobuf_mapper::wrappers")`.
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DoubleValue {
+  #[prost(double, tag = "1")]
+  pub value: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FloatValue {
+  #[prost(float, tag = "1")]
+  pub value: f32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Int64Value {
+  #[prost(int64, tag = "1")]
+  pub value: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct UInt64Value {
+  #[prost(uint64, tag = "1")]
+  pub value: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Int32Value {
+  #[prost(int32, tag = "1")]
+  pub value: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct UInt32Value {
+  #[prost(uint32, tag = "1")]
+  pub value: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BoolValue {
+  #[prost(bool, tag = "1")]
+  pub value: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StringValue {
+  #[prost(string, tag = "1")]
+  pub value: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BytesValue {
+  #[prost(bytes = "vec", tag = "1")]
+  pub value: Vec<u8>,
+}